@@ -2,10 +2,54 @@
 extern crate alloc;
 
 use alloc::string::String;
-use alloy_primitives::{ Address, U256 };
+use alloy_primitives::{ Address, B256, FixedBytes, U8, U64, U256 };
 use alloy_sol_types::sol;
 use core::marker::PhantomData;
-use stylus_sdk::{ evm, msg, prelude::*, block, call::{ call, Call } };
+use stylus_sdk::{
+    abi::Bytes,
+    evm,
+    msg,
+    prelude::*,
+    block,
+    contract,
+    crypto::keccak,
+    call::{ call, static_call, Call },
+    types::AddressVM,
+};
+
+// EIP-712 type strings used to build the permit digest
+const DOMAIN_TYPE: &[u8] =
+    b"EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+const PERMIT_TYPE: &[u8] =
+    b"Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)";
+const DOMAIN_VERSION: &[u8] = b"1";
+
+// pricing modes of the token sale
+const CURVE_FLAT: u8 = 0; // fixed admin `price`
+const CURVE_LINEAR: u8 = 1; // price grows by `slope` wei for every whole token sold
+const CURVE_EXPONENTIAL: u8 = 2; // price grows by `slope` basis points every `step` tokens sold
+const CURVE_ORACLE: u8 = 3; // the oracle's unit price, or `price` when its feed is stale
+
+// fixed point precision used by the exponential curve
+const WAD: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+const BPS: u64 = 10_000;
+
+// ERC-1363 receiver hooks must return their own selector
+const ON_TRANSFER_RECEIVED: [u8; 4] = [0x88, 0xa7, 0xca, 0x5c];
+const ON_APPROVAL_RECEIVED: [u8; 4] = [0x7b, 0x04, 0xa2, 0xd0];
+
+// address of the ecrecover precompile
+const ECRECOVER: Address = Address::new([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+]);
+
+// half the secp256k1 curve order; signatures with a larger `s` are malleable copies
+const SECP256K1N_HALF: U256 = U256::from_limbs([
+    0xdfe92f46681b20a0,
+    0x5d576e7357a4501d,
+    0xffffffffffffffff,
+    0x7fffffffffffffff,
+]);
 
 pub trait Erc20Params {
     /// Immutable token name
@@ -42,18 +86,148 @@ sol_storage! {
         address admin;
         mapping(address => bool) allow_admin;
         uint256 price; // price of the tokens to ethers
-        uint256 market // tokens avialable for sales
+        uint256 market; // tokens avialable for sales
+
+        // EIP-2612 permit nonces; one per signed approval
+        mapping(address => uint256) nonces;
+
+        // bonding curve of the token sale
+        uint8 curve_mode; // one of the CURVE_* modes
+        uint256 curve_base; // wei per token unit when nothing has been sold
+        uint256 curve_slope; // linear: wei per unit added per whole token sold; exponential: growth in bps per step
+        uint256 curve_step; // exponential: token units sold between two price increases
+        uint256 sold; // token units sold so far through the sale
+
+        // ETH reserve backing the sell-back of tokens
+        uint256 reserve; // wei held by this contract for redemptions
+        uint256 reserve_floor; // wei that can never be paid out of the reserve
+        uint256 reserve_bps; // share of every sale kept in the reserve
+        uint256 spread_bps; // discount applied to the curve price when selling back
+
+        // supply control
+        uint256 max_supply; // hard cap on the total supply; zero until set, then immutable
+        mapping(address => MintQuota) mint_quotas; // refilling allowance of each minter
+
+        // vesting
+        mapping(address => Lock) locks; // locked grant of each account
+        mapping(address => bool) platform; // platform contracts allowed to spend locked tokens
+        mapping(address => mapping(address => uint256)) platform_locked; // platform -> account -> locked tokens paid in and not refunded
+
+        // voting power snapshots
+        mapping(address => address) delegates; // who each account delegates its voting power to
+        mapping(address => Checkpoints) vote_checkpoints; // voting power history of each delegate
+        Checkpoints supply_checkpoints; // total supply history
+
+        // merkle airdrops; the distributed tokens are held by this contract until claimed
+        mapping(uint256 => Distribution) distributions;
+        uint256 distribution_count;
+
+        // allowance management
+        mapping(address => mapping(address => AllowanceTerms)) allowance_terms; // expiry and cap of each allowance
+        mapping(address => Spenders) spenders; // spenders each owner has a live allowance for
+
+        // treasury contract receiving the sale proceeds not kept in the reserve; the admin while unset
+        address treasury;
+
+        // oracle contract pricing the token sale in USD
+        address oracle;
+    }
+
+    // optional limits on an allowance; zero means no limit
+    pub struct AllowanceTerms {
+        uint64 expiry; // the allowance can not be spent after this time
+        uint256 per_tx_cap; // most that can be spent in a single transfer_from
+    }
+
+    // enumerable set of spenders; `position` is the index in `list` plus one
+    pub struct Spenders {
+        address[] list;
+        mapping(address => uint256) position;
+    }
+
+    // a merkle airdrop; leaves are keccak256(abi.encodePacked(index, account, amount))
+    pub struct Distribution {
+        bytes32 root;
+        uint256 amount; // tokens set aside for the airdrop
+        uint256 claimed; // tokens claimed so far
+        uint64 expiry; // after this time unclaimed tokens can be reclaimed
+        bool reclaimed;
+        mapping(uint256 => bool) claimed_index;
+    }
+
+    // values recorded at the block they changed in; `blocks` and `values` share indexes
+    pub struct Checkpoints {
+        uint64[] blocks;
+        uint256[] values;
+    }
+
+    // a grant vesting linearly over `duration` seconds from `start`, with nothing vested before the cliff
+    pub struct Lock {
+        uint256 total;
+        uint256 used; // unvested tokens already spent on platform actions
+        uint64 start;
+        uint64 cliff;
+        uint64 duration;
+    }
+
+    // how much a minter can mint; `remaining` refills by `rate` every `period` up to `capacity`
+    pub struct MintQuota {
+        uint256 capacity;
+        uint256 rate;
+        uint64 period;
+        uint256 remaining;
+        uint64 updated;
+    }
+}
+
+sol_interface! {
+    // ERC-1363 receivers, such as ticket sales and safe vote
+    interface IErc1363Receiver {
+        function onTransferReceived(address operator, address from, uint256 value, bytes calldata data) external returns (bytes4);
+    }
 
+    interface IErc1363Spender {
+        function onApprovalReceived(address owner, uint256 value, bytes calldata data) external returns (bytes4);
+    }
+
+    // treasury contract splitting the sale proceeds between the beneficiaries
+    interface ITreasury {
+        function deposit() external payable;
+    }
+
+    // USD pricing of the sale through an ETH/USD feed
+    interface IOracle {
+        function unitPrice() external view returns (uint256);
+        function quoteUsd(uint256 wei) external view returns (uint256);
     }
 }
 
 // Declare events and Solidity error types
 sol! {
-    event Transfer(address indexed from, address indexed to, uint256 value, uint64 time);
-    event Approval(address indexed owner, address indexed spender, uint256 value, uint64 time);
+    // canonical ERC-20 events, recognised by wallets, explorers and indexers
+    event Transfer(address indexed from, address indexed to, uint256 value);
+    event Approval(address indexed owner, address indexed spender, uint256 value);
+
+    // NovaVault analytics events carrying the time of the movement
+    event NovaTransfer(address indexed from, address indexed to, uint256 value, uint64 time);
+    event NovaApproval(address indexed owner, address indexed spender, uint256 value, uint64 time);
     event TokenSold(address indexed buyer, uint256 indexed price, uint256 indexed value, uint256 market, uint64 time);
     event SetMarket(uint256 indexed old_market, uint256 indexed new_market, uint64 time);
     event SetPrice(uint256 indexed old_price, uint256 indexed new_price, uint64 time);
+    event SetCurve(uint8 indexed mode, uint256 base, uint256 slope, uint256 step, uint64 time);
+    event SetReserve(uint256 reserve_bps, uint256 spread_bps, uint256 floor, uint64 time);
+    event SetMaxSupply(uint256 max_supply, uint64 time);
+    event SetMintQuota(address indexed minter, uint256 capacity, uint256 rate, uint64 period, uint64 time);
+    event MintQuotaUsed(address indexed minter, uint256 amount, uint256 remaining, uint64 time);
+    event TokensLocked(address indexed to, uint256 amount, uint64 cliff, uint64 duration, uint64 time);
+    event LockedRefunded(address indexed platform, address indexed to, uint256 amount, uint256 relocked, uint64 time);
+    event DelegateChanged(address indexed delegator, address indexed from_delegate, address indexed to_delegate);
+    event DelegateVotesChanged(address indexed delegate, uint256 previous_votes, uint256 new_votes);
+    event DistributionCreated(uint256 indexed distribution_id, bytes32 root, uint256 amount, uint64 expiry, uint64 time);
+    event Claimed(uint256 indexed distribution_id, uint256 index, address indexed account, uint256 amount, uint64 time);
+    event Reclaimed(uint256 indexed distribution_id, uint256 amount, uint64 time);
+    event AllowanceTermsSet(address indexed owner, address indexed spender, uint64 expiry, uint256 per_tx_cap, uint64 time);
+    event TokenRedeemed(address indexed seller, uint256 indexed amount, uint256 indexed value, uint256 reserve, uint64 time);
 
     error InsufficientBalance(address from, uint256 have, uint256 want);
     error InsufficientAllowance(address owner, address spender, uint256 have, uint256 want);
@@ -61,6 +235,21 @@ sol! {
     error InsufficientFunds(address from, uint256 value, uint256 price);
     error MarketExceeded(address from, uint256 market, uint256 amount);
     error Unauthorized(uint8 point);
+    error PermitExpired(uint256 deadline, uint64 time);
+    error InvalidSigner(address signer, address owner);
+    error SlippageExceeded(uint256 cost, uint256 max_cost);
+    error InsufficientOutput(uint256 value, uint256 min_value);
+    error ReserveFloor(uint256 reserve, uint256 floor, uint256 value);
+    error MintQuotaExceeded(address minter, uint256 available, uint256 amount);
+    error MaxSupplyExceeded(uint256 max_supply, uint256 total_supply, uint256 amount);
+    error LockedBalance(address from, uint256 free, uint256 want);
+    error FutureLookup(uint64 block, uint64 current);
+    error ReceiverRejected(address receiver);
+    error InvalidProof(uint256 distribution_id, uint256 index);
+    error AlreadyClaimed(uint256 distribution_id, uint256 index);
+    error ClaimWindow(uint256 distribution_id, uint64 expiry);
+    error AllowanceExpired(address owner, address spender, uint64 expiry);
+    error AllowanceCapExceeded(address owner, address spender, uint256 cap, uint256 value);
 
 }
 
@@ -73,6 +262,21 @@ pub enum Erc20Error {
     InsufficientFunds(InsufficientFunds),
     MarketExceeded(MarketExceeded),
     Unauthorized(Unauthorized),
+    PermitExpired(PermitExpired),
+    InvalidSigner(InvalidSigner),
+    SlippageExceeded(SlippageExceeded),
+    InsufficientOutput(InsufficientOutput),
+    ReserveFloor(ReserveFloor),
+    MintQuotaExceeded(MintQuotaExceeded),
+    MaxSupplyExceeded(MaxSupplyExceeded),
+    LockedBalance(LockedBalance),
+    FutureLookup(FutureLookup),
+    ReceiverRejected(ReceiverRejected),
+    InvalidProof(InvalidProof),
+    AlreadyClaimed(AlreadyClaimed),
+    ClaimWindow(ClaimWindow),
+    AllowanceExpired(AllowanceExpired),
+    AllowanceCapExceeded(AllowanceCapExceeded),
 }
 
 // These methods are external to other contracts
@@ -118,15 +322,40 @@ impl Erc20 {
         to: Address,
         value: U256
     ) -> Result<bool, Erc20Error> {
+        let spender = msg::sender();
+
+        // Check the allowance terms
+        let terms = self.allowance_terms.getter(from);
+        let terms = terms.getter(spender);
+        let expiry = terms.expiry.get().to::<u64>();
+        if expiry != 0 && expiry < block::timestamp() {
+            return Err(
+                Erc20Error::AllowanceExpired(AllowanceExpired {
+                    owner: from,
+                    spender,
+                    expiry,
+                })
+            );
+        }
+        let cap = terms.per_tx_cap.get();
+        if cap != U256::ZERO && value > cap {
+            return Err(
+                Erc20Error::AllowanceCapExceeded(AllowanceCapExceeded {
+                    owner: from,
+                    spender,
+                    cap,
+                    value,
+                })
+            );
+        }
+
         // Check msg::sender() allowance
-        let mut sender_allowances = self.allowances.setter(from);
-        let mut allowance = sender_allowances.setter(msg::sender());
-        let old_allowance = allowance.get();
+        let old_allowance = self.allowances.getter(from).get(spender);
         if old_allowance < value {
             return Err(
                 Erc20Error::InsufficientAllowance(InsufficientAllowance {
                     owner: from,
-                    spender: msg::sender(),
+                    spender,
                     have: old_allowance,
                     want: value,
                 })
@@ -134,7 +363,8 @@ impl Erc20 {
         }
 
         // Decreases allowance
-        allowance.set(old_allowance - value);
+        self.allowances.setter(from).insert(spender, old_allowance - value);
+        self.track_spender(from, spender, old_allowance - value);
 
         // Calls the internal transfer function
         self._transfer(from, to, value)?;
@@ -143,22 +373,230 @@ impl Erc20 {
     }
 
     /// Approves the spenditure of `value` tokens of msg::sender() to `spender`
+    /// (replaces the allowance along with any expiry or cap it had)
     pub fn approve(&mut self, spender: Address, value: U256) -> bool {
-        self.allowances.setter(msg::sender()).insert(spender, value);
-        evm::log(Approval {
-            owner: msg::sender(),
-            spender,
-            value,
-            time: block::timestamp() as u64,
-        });
+        self.set_terms(msg::sender(), spender, 0, U256::ZERO);
+        self._approve(msg::sender(), spender, value);
+        true
+    }
+
+    /// Approves `value` tokens to `spender` until `expiry`, spendable at most `per_tx_cap` at a time;
+    /// a zero expiry or cap means no limit
+    pub fn approve_with_terms(
+        &mut self,
+        spender: Address,
+        value: U256,
+        expiry: u64,
+        per_tx_cap: U256
+    ) -> Result<bool, Erc20Error> {
+        if expiry != 0 && expiry <= block::timestamp() {
+            return Err(
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 29,
+                })
+            );
+        }
+
+        self.set_terms(msg::sender(), spender, expiry, per_tx_cap);
+        self._approve(msg::sender(), spender, value);
+        Ok(true)
+    }
+
+    /// Raises the allowance of `spender` by `added`, keeping its terms
+    pub fn increase_allowance(&mut self, spender: Address, added: U256) -> Result<bool, Erc20Error> {
+        let owner = msg::sender();
+        let value = self.allowances.getter(owner).get(spender);
+        let value = value.checked_add(added).ok_or(
+            Erc20Error::InvalidParameter(InvalidParameter {
+                point: 30,
+            })
+        )?;
+        self._approve(owner, spender, value);
+        Ok(true)
+    }
+
+    /// Lowers the allowance of `spender` by `subtracted`, keeping its terms
+    pub fn decrease_allowance(
+        &mut self,
+        spender: Address,
+        subtracted: U256
+    ) -> Result<bool, Erc20Error> {
+        let owner = msg::sender();
+        let value = self.allowances.getter(owner).get(spender);
+        if value < subtracted {
+            return Err(
+                Erc20Error::InsufficientAllowance(InsufficientAllowance {
+                    owner,
+                    spender,
+                    have: value,
+                    want: subtracted,
+                })
+            );
+        }
+        self._approve(owner, spender, value - subtracted);
+        Ok(true)
+    }
+
+    /// Sets the allowance of every spender in `spenders` to zero
+    pub fn revoke_all(&mut self, spenders: Vec<Address>) -> bool {
+        let owner = msg::sender();
+        for spender in spenders {
+            self.set_terms(owner, spender, 0, U256::ZERO);
+            self._approve(owner, spender, U256::ZERO);
+        }
         true
     }
 
-    /// Returns the allowance of `spender` on `owner`'s tokens
+    /// Returns the allowance of `spender` on `owner`'s tokens (zero once it has expired)
     pub fn allowance(&self, owner: Address, spender: Address) -> U256 {
+        let expiry = self.allowance_terms.getter(owner).getter(spender).expiry.get().to::<u64>();
+        if expiry != 0 && expiry < block::timestamp() {
+            return U256::ZERO;
+        }
         self.allowances.getter(owner).get(spender)
     }
 
+    /// Returns (value, expiry, per_tx_cap) of the allowance of `spender` on `owner`'s tokens
+    pub fn allowance_terms(&self, owner: Address, spender: Address) -> (U256, u64, U256) {
+        let terms = self.allowance_terms.getter(owner);
+        let terms = terms.getter(spender);
+        (
+            self.allowances.getter(owner).get(spender),
+            terms.expiry.get().to::<u64>(),
+            terms.per_tx_cap.get(),
+        )
+    }
+
+    /// Returns the spenders `owner` has a non zero allowance for
+    pub fn spenders_of(&self, owner: Address) -> Vec<Address> {
+        let spenders = self.spenders.getter(owner);
+        (0..spenders.list.len()).filter_map(|i| spenders.list.get(i)).collect()
+    }
+
+    /// ERC-1363: transfers `value` tokens to the contract `to` and calls its
+    /// `onTransferReceived` hook with `data`, e.g. to buy a ticket or cast a vote in one transaction
+    pub fn transfer_and_call(
+        &mut self,
+        to: Address,
+        value: U256,
+        data: Bytes
+    ) -> Result<bool, Erc20Error> {
+        let from = msg::sender();
+        let paid_in = self.platform_locked.getter(to).get(from);
+        self._transfer(from, to, value)?;
+
+        if !to.has_code() {
+            return Err(Erc20Error::ReceiverRejected(ReceiverRejected { receiver: to }));
+        }
+        // a platform is told how much of the payment came from a locked grant in the last 32 bytes
+        // of `data`, since it can not call back into the token from inside the hook
+        let mut data = data.0.to_vec();
+        if self.platform.get(to) {
+            let locked = self.platform_locked.getter(to).get(from) - paid_in;
+            data.extend_from_slice(&locked.to_be_bytes::<32>());
+        }
+        let receiver = IErc1363Receiver::new(to);
+        let config = Call::new_in(self);
+        match receiver.on_transfer_received(config, from, from, value, data.into()) {
+            Ok(selector) if selector == FixedBytes(ON_TRANSFER_RECEIVED) => Ok(true),
+            _ => Err(Erc20Error::ReceiverRejected(ReceiverRejected { receiver: to })),
+        }
+    }
+
+    /// ERC-1363: approves `value` tokens to the contract `spender` and calls its
+    /// `onApprovalReceived` hook with `data`
+    pub fn approve_and_call(
+        &mut self,
+        spender: Address,
+        value: U256,
+        data: Bytes
+    ) -> Result<bool, Erc20Error> {
+        let owner = msg::sender();
+        self.set_terms(owner, spender, 0, U256::ZERO);
+        self._approve(owner, spender, value);
+
+        if !spender.has_code() {
+            return Err(Erc20Error::ReceiverRejected(ReceiverRejected { receiver: spender }));
+        }
+        let receiver = IErc1363Spender::new(spender);
+        let config = Call::new_in(self);
+        match receiver.on_approval_received(config, owner, value, data.0.into()) {
+            Ok(selector) if selector == FixedBytes(ON_APPROVAL_RECEIVED) => Ok(true),
+            _ => Err(Erc20Error::ReceiverRejected(ReceiverRejected { receiver: spender })),
+        }
+    }
+
+    /// EIP-2612: sets `value` as the allowance of `spender` over `owner`'s tokens,
+    /// using a signature made off-chain by `owner` instead of an `approve` transaction.
+    /// Platform contracts that spend right after ignore a failed permit, since it may have
+    /// been front-run by someone submitting the same signature; `transferFrom` still checks the allowance.
+    /// They encode the call by hand, as `sol_interface!` hashes `bytes32` as `bytes[32]` into a wrong selector
+    #[allow(clippy::too_many_arguments)]
+    pub fn permit(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: B256,
+        s: B256
+    ) -> Result<(), Erc20Error> {
+        let time = block::timestamp();
+        if U256::from(time) > deadline {
+            return Err(Erc20Error::PermitExpired(PermitExpired { deadline, time }));
+        }
+
+        let nonce = self.nonces.get(owner);
+        let struct_hash = keccak(
+            [
+                keccak(PERMIT_TYPE).as_slice(),
+                B256::left_padding_from(owner.as_slice()).as_slice(),
+                B256::left_padding_from(spender.as_slice()).as_slice(),
+                value.to_be_bytes::<32>().as_slice(),
+                nonce.to_be_bytes::<32>().as_slice(),
+                deadline.to_be_bytes::<32>().as_slice(),
+            ].concat()
+        );
+        let digest = keccak(
+            [
+                [0x19u8, 0x01].as_slice(),
+                self.domain_separator().as_slice(),
+                struct_hash.as_slice(),
+            ].concat()
+        );
+
+        // the signer must be the owner; a zero address means the signature was invalid
+        let signer = Self::recover(digest, v, r, s);
+        if signer == Address::ZERO || signer != owner {
+            return Err(Erc20Error::InvalidSigner(InvalidSigner { signer, owner }));
+        }
+
+        self.nonces.setter(owner).set(nonce + U256::from(1));
+        self.set_terms(owner, spender, 0, U256::ZERO);
+        self._approve(owner, spender, value);
+        Ok(())
+    }
+
+    /// Current permit nonce of `owner`
+    pub fn nonces(&self, owner: Address) -> U256 {
+        self.nonces.get(owner)
+    }
+
+    /// EIP-712 domain separator used by `permit`
+    #[selector(name = "DOMAIN_SEPARATOR")]
+    pub fn domain_separator(&self) -> B256 {
+        keccak(
+            [
+                keccak(DOMAIN_TYPE).as_slice(),
+                keccak(NovaParams::NAME.as_bytes()).as_slice(),
+                keccak(DOMAIN_VERSION).as_slice(),
+                U256::from(block::chainid()).to_be_bytes::<32>().as_slice(),
+                B256::left_padding_from(contract::address().as_slice()).as_slice(),
+            ].concat()
+        )
+    }
+
     pub fn set_mint(&mut self, users: Vec<Address>, state: Vec<bool>) -> Result<(), Erc20Error> {
         self.check_admin()?;
 
@@ -195,20 +633,100 @@ impl Erc20 {
         Ok(())
     }
 
+    /// Sets the pricing mode of the token sale and its curve parameters
+    pub fn set_curve(
+        &mut self,
+        mode: u8,
+        base: U256,
+        slope: U256,
+        step: U256
+    ) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        if
+            mode > CURVE_ORACLE ||
+            (mode == CURVE_EXPONENTIAL && step == U256::ZERO) ||
+            (mode == CURVE_ORACLE && self.oracle.get() == Address::ZERO)
+        {
+            return Err(
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 20,
+                })
+            );
+        }
+
+        self.curve_mode.set(U8::from(mode));
+        self.curve_base.set(base);
+        self.curve_slope.set(slope);
+        self.curve_step.set(step);
+        evm::log(SetCurve {
+            mode,
+            base,
+            slope,
+            step,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    /// Returns (mode, base, slope, step, sold) of the token sale
+    pub fn get_curve(&self) -> (u8, U256, U256, U256, U256) {
+        (
+            self.curve_mode.get().to::<u8>(),
+            self.curve_base.get(),
+            self.curve_slope.get(),
+            self.curve_step.get(),
+            self.sold.get(),
+        )
+    }
+
+    /// Sets the oracle contract pricing the sale in USD, used by the oracle pricing mode
+    pub fn set_oracle(&mut self, oracle: Address) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        self.oracle.set(oracle);
+        Ok(())
+    }
+
+    /// Returns the oracle contract pricing the sale in USD
+    pub fn get_oracle(&self) -> Address {
+        self.oracle.get()
+    }
+
+    /// Returns the current price of one whole token as (USD with 18 decimals, wei, whether the feed is live);
+    /// the USD price is zero when the feed is stale
+    pub fn quote_price(&self) -> Result<(U256, U256, bool), Erc20Error> {
+        let unit = U256::from(10).pow(U256::from(NovaParams::DECIMALS));
+        let wei_price = self.get_price()?.saturating_mul(unit);
+        let oracle = self.oracle.get();
+        if oracle == Address::ZERO {
+            return Ok((U256::ZERO, wei_price, false));
+        }
+        match IOracle::new(oracle).quote_usd(Call::new(), wei_price) {
+            Ok(usd_price) => Ok((usd_price, wei_price, true)),
+            Err(_e) => Ok((U256::ZERO, wei_price, false)),
+        }
+    }
+
     pub fn get_market(&self) -> U256 {
         self.market.get()
     }
 
-    pub fn get_price(&self) -> U256 {
-        self.price.get()
+    /// Current price of one token unit
+    pub fn get_price(&self) -> Result<U256, Erc20Error> {
+        self.cost_of(self.sold.get(), U256::from(1))
     }
 
+    /// Cost in wei of buying `amount` token units now
+    pub fn quote_buy(&self, amount: U256) -> Result<U256, Erc20Error> {
+        self.cost_of(self.sold.get(), amount)
+    }
+
+    /// Buys as many tokens as `msg::value()` can pay for and refunds the leftover wei
     #[payable]
+    #[allow(non_snake_case)]
     pub fn BUY(&mut self) -> Result<(), Erc20Error> {
         let value = msg::value();
-        let price = self.price.get();
-        let admin = self.admin.get();
-        if price > value {
+        let price = self.get_price()?;
+        if price == U256::ZERO || price > value {
             return Err(
                 Erc20Error::InsufficientFunds(InsufficientFunds {
                     from: msg::sender(),
@@ -218,39 +736,297 @@ impl Erc20 {
             );
         }
 
-        let amount = value / price;
-        let market = self.market.get();
-        if amount > market {
+        let amount = self.affordable(value, price);
+        let cost = self.quote_buy(amount)?;
+        self.fill_buy(amount, cost, value, price)
+    }
+
+    /// Buys exactly `amount` token units, failing if they cost more than `max_cost`;
+    /// the leftover wei is refunded
+    #[payable]
+    pub fn buy_exact(&mut self, amount: U256, max_cost: U256) -> Result<(), Erc20Error> {
+        let value = msg::value();
+        let price = self.get_price()?;
+        let cost = self.quote_buy(amount)?;
+        if amount == U256::ZERO || cost == U256::ZERO {
             return Err(
-                Erc20Error::MarketExceeded(MarketExceeded {
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 22,
+                })
+            );
+        }
+        if cost > max_cost {
+            return Err(Erc20Error::SlippageExceeded(SlippageExceeded { cost, max_cost }));
+        }
+        if cost > value {
+            return Err(
+                Erc20Error::InsufficientFunds(InsufficientFunds {
                     from: msg::sender(),
-                    market,
-                    amount,
+                    value,
+                    price: cost,
                 })
             );
         }
 
-        self.market.set(market - amount);
+        self.fill_buy(amount, cost, value, price)
+    }
+
+    /// Sets the share of each sale kept in the reserve, the sell-back spread
+    /// and the floor the reserve can never be drained below
+    pub fn set_reserve(
+        &mut self,
+        reserve_bps: U256,
+        spread_bps: U256,
+        floor: U256
+    ) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        if reserve_bps > U256::from(BPS) || spread_bps > U256::from(BPS) {
+            return Err(
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 23,
+                })
+            );
+        }
+
+        self.reserve_bps.set(reserve_bps);
+        self.spread_bps.set(spread_bps);
+        self.reserve_floor.set(floor);
+        evm::log(SetReserve {
+            reserve_bps,
+            spread_bps,
+            floor,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    /// Sets the treasury contract the sale proceeds not kept in the reserve are sent to
+    pub fn set_treasury(&mut self, treasury: Address) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        self.treasury.set(treasury);
+        Ok(())
+    }
+
+    /// Returns the treasury contract; the proceeds go to the admin while it is unset
+    pub fn get_treasury(&self) -> Address {
+        self.treasury.get()
+    }
 
-        let _ = self._mint(msg::sender(), amount)?;
+    /// Returns (reserve, floor, reserve_bps, spread_bps)
+    pub fn get_reserve(&self) -> (U256, U256, U256, U256) {
+        (
+            self.reserve.get(),
+            self.reserve_floor.get(),
+            self.reserve_bps.get(),
+            self.spread_bps.get(),
+        )
+    }
+
+    /// Reserve held against the current value of the supply, in basis points
+    pub fn reserve_ratio(&self) -> Result<U256, Erc20Error> {
+        let supply_value = self.get_price()?.saturating_mul(self.total_supply.get());
+        if supply_value == U256::ZERO {
+            return Ok(U256::ZERO);
+        }
+        Ok(self.reserve.get().saturating_mul(U256::from(BPS)) / supply_value)
+    }
 
-        let _ = call(Call::new_in(self).value(value), admin, &[]).map_err(|_e| {
+    /// Wei paid out of the reserve for selling back `amount` token units now
+    pub fn quote_sell(&self, amount: U256) -> Result<U256, Erc20Error> {
+        // units beyond what the sale has sold (airdrops, rewards) are priced at the bottom of the curve
+        let sold = self.sold.get();
+        let on_curve = amount.min(sold);
+        let floor_price = self.cost_of(U256::ZERO, U256::from(1))?;
+        let value = self
+            .cost_of(sold - on_curve, on_curve)?
+            .saturating_add(floor_price.saturating_mul(amount - on_curve));
+        let overflow = || Erc20Error::InvalidParameter(InvalidParameter { point: 21 });
+        let value = value.checked_mul(U256::from(BPS) - self.spread_bps.get()).ok_or_else(overflow)?;
+        Ok(value / U256::from(BPS))
+    }
+
+    /// Burns `amount` tokens of the caller and pays them from the reserve,
+    /// failing if they are worth less than `min_eth_out`
+    pub fn sell(&mut self, amount: U256, min_eth_out: U256) -> Result<(), Erc20Error> {
+        let value = self.quote_sell(amount)?;
+        if amount == U256::ZERO || value < min_eth_out {
+            return Err(
+                Erc20Error::InsufficientOutput(InsufficientOutput {
+                    value,
+                    min_value: min_eth_out,
+                })
+            );
+        }
+
+        let reserve = self.reserve.get();
+        let floor = self.reserve_floor.get();
+        if reserve < value || reserve - value < floor {
+            return Err(Erc20Error::ReserveFloor(ReserveFloor { reserve, floor, value }));
+        }
+
+        self._burn(msg::sender(), amount)?;
+
+        // the tokens go back on sale and the curve steps down
+        self.reserve.set(reserve - value);
+        self.sold.set(self.sold.get().saturating_sub(amount));
+        self.market.set(self.market.get() + amount);
+
+        call(Call::new_in(self).value(value), msg::sender(), &[]).map_err(|_e| {
             Erc20Error::InvalidParameter(InvalidParameter {
-                point: 11,
+                point: 12,
             })
-        });
+        })?;
 
-        evm::log(TokenSold {
-            buyer: msg::sender(),
-            price,
+        evm::log(TokenRedeemed {
+            seller: msg::sender(),
+            amount,
             value,
-            market,
+            reserve: reserve - value,
             time: block::timestamp() as u64,
         });
+        Ok(())
+    }
+
+    /// Sends `amounts[i]` of the caller's tokens to `recipients[i]` in a single transaction
+    pub fn multi_transfer(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>
+    ) -> Result<bool, Erc20Error> {
+        if recipients.len() != amounts.len() {
+            return Err(
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 27,
+                })
+            );
+        }
+
+        for (to, value) in recipients.into_iter().zip(amounts.into_iter()) {
+            self._transfer(msg::sender(), to, value)?;
+        }
+        Ok(true)
+    }
 
+    /// Publishes a merkle airdrop of `amount` of the admin's tokens, claimable until `expiry`
+    pub fn create_distribution(
+        &mut self,
+        root: B256,
+        amount: U256,
+        expiry: u64
+    ) -> Result<U256, Erc20Error> {
+        self.check_admin()?;
+        if root == B256::ZERO || expiry <= block::timestamp() {
+            return Err(
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 28,
+                })
+            );
+        }
+
+        self._transfer(msg::sender(), contract::address(), amount)?;
+
+        // starting index from 1 to reduce paralax error
+        let distribution_id = self.distribution_count.get() + U256::from(1);
+        let mut distribution = self.distributions.setter(distribution_id);
+        distribution.root.set(root);
+        distribution.amount.set(amount);
+        distribution.expiry.set(U64::from(expiry));
+        self.distribution_count.set(distribution_id);
+
+        evm::log(DistributionCreated {
+            distribution_id,
+            root,
+            amount,
+            expiry,
+            time: block::timestamp() as u64,
+        });
+        Ok(distribution_id)
+    }
+
+    /// Claims `amount` tokens of an airdrop for the caller, proving leaf `index` is in the merkle root
+    pub fn claim(
+        &mut self,
+        distribution_id: U256,
+        index: U256,
+        amount: U256,
+        proof: Vec<B256>
+    ) -> Result<(), Erc20Error> {
+        let account = msg::sender();
+        let distribution = self.distributions.getter(distribution_id);
+        let expiry = distribution.expiry.get().to::<u64>();
+        if expiry < block::timestamp() || distribution.reclaimed.get() {
+            return Err(Erc20Error::ClaimWindow(ClaimWindow { distribution_id, expiry }));
+        }
+        if distribution.claimed_index.get(index) {
+            return Err(Erc20Error::AlreadyClaimed(AlreadyClaimed { distribution_id, index }));
+        }
+
+        let leaf = keccak(
+            [
+                index.to_be_bytes::<32>().as_slice(),
+                account.as_slice(),
+                amount.to_be_bytes::<32>().as_slice(),
+            ].concat()
+        );
+        let root = distribution.root.get();
+        let claimed = distribution.claimed.get();
+        if Self::proof_root(leaf, &proof) != root || claimed + amount > distribution.amount.get() {
+            return Err(Erc20Error::InvalidProof(InvalidProof { distribution_id, index }));
+        }
+
+        let mut distribution = self.distributions.setter(distribution_id);
+        distribution.claimed_index.setter(index).set(true);
+        distribution.claimed.set(claimed + amount);
+        self._transfer(contract::address(), account, amount)?;
+
+        evm::log(Claimed {
+            distribution_id,
+            index,
+            account,
+            amount,
+            time: block::timestamp() as u64,
+        });
         Ok(())
     }
 
+    /// Sends what is left of an expired airdrop back to the admin
+    pub fn reclaim(&mut self, distribution_id: U256) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        let distribution = self.distributions.getter(distribution_id);
+        let expiry = distribution.expiry.get().to::<u64>();
+        if expiry == 0 || expiry >= block::timestamp() || distribution.reclaimed.get() {
+            return Err(Erc20Error::ClaimWindow(ClaimWindow { distribution_id, expiry }));
+        }
+
+        let amount = distribution.amount.get() - distribution.claimed.get();
+        self.distributions.setter(distribution_id).reclaimed.set(true);
+        self._transfer(contract::address(), msg::sender(), amount)?;
+
+        evm::log(Reclaimed {
+            distribution_id,
+            amount,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    /// Returns (root, amount, claimed, expiry, reclaimed) of an airdrop
+    pub fn get_distribution(&self, distribution_id: U256) -> (B256, U256, U256, u64, bool) {
+        let distribution = self.distributions.getter(distribution_id);
+        (
+            distribution.root.get(),
+            distribution.amount.get(),
+            distribution.claimed.get(),
+            distribution.expiry.get().to::<u64>(),
+            distribution.reclaimed.get(),
+        )
+    }
+
+    /// Whether leaf `index` of an airdrop has been claimed
+    pub fn is_claimed(&self, distribution_id: U256, index: U256) -> bool {
+        self.distributions.getter(distribution_id).claimed_index.get(index)
+    }
+
     pub fn mint(&mut self, value: U256) -> Result<(), Erc20Error> {
         let user_state = self.allow_admin.getter(msg::sender()).get();
         if !user_state {
@@ -260,6 +1036,7 @@ impl Erc20 {
                 })
             );
         }
+        self.use_quota(msg::sender(), value)?;
         self._mint(msg::sender(), value)?;
         Ok(())
     }
@@ -274,10 +1051,251 @@ impl Erc20 {
                 })
             );
         }
+        self.use_quota(msg::sender(), value)?;
         self._mint(to, value)?;
         Ok(())
     }
 
+    /// Mints `value` tokens to `to` that vest linearly over `duration` seconds,
+    /// with nothing vested before `cliff` seconds; what is still locked from an
+    /// earlier grant is folded into the new schedule
+    pub fn mint_locked(
+        &mut self,
+        to: Address,
+        value: U256,
+        cliff: u64,
+        duration: u64
+    ) -> Result<(), Erc20Error> {
+        let user_state = self.allow_admin.getter(msg::sender()).get();
+        if !user_state {
+            return Err(
+                Erc20Error::Unauthorized(Unauthorized {
+                    point: 2,
+                })
+            );
+        }
+        if duration == 0 || cliff > duration {
+            return Err(
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 25,
+                })
+            );
+        }
+
+        self.use_quota(msg::sender(), value)?;
+        self._mint(to, value)?;
+
+        let still_locked = self.locked_balance_of(to);
+        let mut lock = self.locks.setter(to);
+        lock.total.set(still_locked + value);
+        lock.used.set(U256::ZERO);
+        lock.start.set(U64::from(block::timestamp()));
+        lock.cliff.set(U64::from(cliff));
+        lock.duration.set(U64::from(duration));
+
+        evm::log(TokensLocked {
+            to,
+            amount: value,
+            cliff,
+            duration,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    /// Tokens of `account`'s grant that have vested so far
+    pub fn vested_of(&self, account: Address) -> U256 {
+        let lock = self.locks.getter(account);
+        let total = lock.total.get();
+        let start = lock.start.get().to::<u64>();
+        let duration = lock.duration.get().to::<u64>();
+        let elapsed = block::timestamp().saturating_sub(start);
+
+        if elapsed < lock.cliff.get().to::<u64>() {
+            return U256::ZERO;
+        }
+        if elapsed >= duration {
+            return total;
+        }
+        (total * U256::from(elapsed)) / U256::from(duration)
+    }
+
+    /// Tokens of `account` that can not be moved yet
+    pub fn locked_balance_of(&self, account: Address) -> U256 {
+        let lock = self.locks.getter(account);
+        lock.total
+            .get()
+            .saturating_sub(self.vested_of(account))
+            .saturating_sub(lock.used.get())
+    }
+
+    /// Sets the platform contracts (ticket sales, safe vote, patronage) locked tokens can be paid into
+    pub fn set_platform(
+        &mut self,
+        contracts: Vec<Address>,
+        state: Vec<bool>
+    ) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        for (ad, bo) in contracts.into_iter().zip(state.into_iter()) {
+            self.platform.setter(ad).set(bo);
+        }
+        Ok(())
+    }
+
+    /// Locked tokens `account` paid into `platform` that it has not refunded
+    pub fn platform_locked_of(&self, platform: Address, account: Address) -> U256 {
+        self.platform_locked.getter(platform).get(account)
+    }
+
+    /// Lets a platform contract refund `value` of its tokens to `to`; up to `locked` of them
+    /// go back under `to`'s lock, as far as they were paid in locked and the grant has used them.
+    /// Returns how many tokens were locked again
+    pub fn refund_locked(
+        &mut self,
+        to: Address,
+        value: U256,
+        locked: U256
+    ) -> Result<U256, Erc20Error> {
+        let platform = msg::sender();
+        if !self.platform.get(platform) {
+            return Err(
+                Erc20Error::Unauthorized(Unauthorized {
+                    point: 3,
+                })
+            );
+        }
+
+        self._transfer(platform, to, value)?;
+
+        let paid_in = self.platform_locked.getter(platform).get(to);
+        let mut lock = self.locks.setter(to);
+        let used = lock.used.get();
+        let relocked = locked.min(value).min(paid_in).min(used);
+        if relocked != U256::ZERO {
+            lock.used.set(used - relocked);
+            self.platform_locked.setter(platform).setter(to).set(paid_in - relocked);
+        }
+
+        evm::log(LockedRefunded {
+            platform,
+            to,
+            amount: value,
+            relocked,
+            time: block::timestamp() as u64,
+        });
+        Ok(relocked)
+    }
+
+    /// Address `account` delegates its voting power to
+    pub fn delegates(&self, account: Address) -> Address {
+        self.delegates.get(account)
+    }
+
+    /// Delegates the caller's voting power to `delegatee`; delegate to yourself to vote with your own balance
+    pub fn delegate(&mut self, delegatee: Address) {
+        let delegator = msg::sender();
+        let from_delegate = self.delegates.get(delegator);
+        self.delegates.setter(delegator).set(delegatee);
+
+        evm::log(DelegateChanged {
+            delegator,
+            from_delegate,
+            to_delegate: delegatee,
+        });
+
+        let balance = self.balances.get(delegator);
+        self.move_votes(from_delegate, delegatee, balance);
+    }
+
+    /// Current voting power of `account`
+    pub fn get_votes(&self, account: Address) -> U256 {
+        self.vote_checkpoints.getter(account).latest()
+    }
+
+    /// Voting power `account` had at the end of `block`
+    pub fn get_past_votes(&self, account: Address, block: u64) -> Result<U256, Erc20Error> {
+        Self::check_past(block)?;
+        Ok(self.vote_checkpoints.getter(account).upper_lookup(block))
+    }
+
+    /// Total supply at the end of `block`
+    pub fn get_past_total_supply(&self, block: u64) -> Result<U256, Erc20Error> {
+        Self::check_past(block)?;
+        Ok(self.supply_checkpoints.upper_lookup(block))
+    }
+
+    /// Sets the hard cap on the total supply; it can only be set once
+    pub fn set_max_supply(&mut self, max_supply: U256) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        if
+            self.max_supply.get() != U256::ZERO ||
+            max_supply == U256::ZERO ||
+            max_supply < self.total_supply.get()
+        {
+            return Err(
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 24,
+                })
+            );
+        }
+
+        self.max_supply.set(max_supply);
+        evm::log(SetMaxSupply {
+            max_supply,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    /// Hard cap on the total supply (zero while unset)
+    pub fn max_supply(&self) -> U256 {
+        self.max_supply.get()
+    }
+
+    /// Sets how much `minter` can mint: up to `capacity` at once,
+    /// refilling by `rate` tokens every `period` seconds (no refill when `period` is zero);
+    /// a zero `capacity` removes the quota
+    pub fn set_mint_quota(
+        &mut self,
+        minter: Address,
+        capacity: U256,
+        rate: U256,
+        period: u64
+    ) -> Result<(), Erc20Error> {
+        self.check_admin()?;
+        let mut quota = self.mint_quotas.setter(minter);
+        quota.capacity.set(capacity);
+        quota.rate.set(rate);
+        quota.period.set(U64::from(period));
+        quota.remaining.set(capacity);
+        quota.updated.set(U64::from(block::timestamp()));
+
+        evm::log(SetMintQuota {
+            minter,
+            capacity,
+            rate,
+            period,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    /// Amount `minter` can mint right now; unlimited for a minter without a quota
+    pub fn mint_quota_of(&self, minter: Address) -> U256 {
+        self.refilled_quota(minter).0
+    }
+
+    /// Returns (capacity, rate, period, available now) of `minter`'s quota
+    pub fn get_mint_quota(&self, minter: Address) -> (U256, U256, u64, U256) {
+        let quota = self.mint_quotas.getter(minter);
+        (
+            quota.capacity.get(),
+            quota.rate.get(),
+            quota.period.get().to::<u64>(),
+            self.mint_quota_of(minter),
+        )
+    }
+
     /// Burns tokens
     pub fn burn(&mut self, value: U256) -> Result<(), Erc20Error> {
         self._burn(msg::sender(), value)?;
@@ -289,12 +1307,97 @@ impl Erc20 {
 // Methods marked as "pub" here are usable outside of the erc20 module (i.e. they're callable from lib.rs)
 // Note: modifying storage will become much prettier soon
 impl Erc20 {
+    /// Sets the allowance of `spender` over `owner`'s tokens
+    /// (invoked by the external approve() and permit() functions)
+    pub fn _approve(&mut self, owner: Address, spender: Address, value: U256) {
+        self.allowances.setter(owner).insert(spender, value);
+        self.track_spender(owner, spender, value);
+        evm::log(Approval { owner, spender, value });
+        evm::log(NovaApproval {
+            owner,
+            spender,
+            value,
+            time: block::timestamp() as u64,
+        });
+    }
+
+    /// Sets the expiry and per transaction cap of the allowance of `spender` over `owner`'s tokens
+    pub fn set_terms(&mut self, owner: Address, spender: Address, expiry: u64, per_tx_cap: U256) {
+        let mut terms = self.allowance_terms.setter(owner);
+        let mut terms = terms.setter(spender);
+        if terms.expiry.get().to::<u64>() == expiry && terms.per_tx_cap.get() == per_tx_cap {
+            return;
+        }
+        terms.expiry.set(U64::from(expiry));
+        terms.per_tx_cap.set(per_tx_cap);
+
+        evm::log(AllowanceTermsSet {
+            owner,
+            spender,
+            expiry,
+            per_tx_cap,
+            time: block::timestamp() as u64,
+        });
+    }
+
+    /// Keeps `spender` listed under `owner` while its allowance is non zero
+    pub fn track_spender(&mut self, owner: Address, spender: Address, value: U256) {
+        let mut spenders = self.spenders.setter(owner);
+        let position = spenders.position.get(spender);
+
+        if value != U256::ZERO {
+            if position == U256::ZERO {
+                spenders.list.push(spender);
+                let len = U256::from(spenders.list.len());
+                spenders.position.setter(spender).set(len);
+            }
+            return;
+        }
+        if position == U256::ZERO {
+            return;
+        }
+
+        // swap the last spender into the removed slot
+        let index = position.to::<usize>() - 1;
+        let last_index = spenders.list.len() - 1;
+        if index != last_index {
+            let last = spenders.list.get(last_index).unwrap_or_default();
+            if let Some(mut slot) = spenders.list.setter(index) {
+                slot.set(last);
+            }
+            spenders.position.setter(last).set(position);
+        }
+        spenders.list.pop();
+        spenders.position.setter(spender).set(U256::ZERO);
+    }
+
+    /// Recovers the signer of `digest` through the ecrecover precompile;
+    /// returns the zero address when the signature can not be recovered
+    pub fn recover(digest: B256, v: u8, r: B256, s: B256) -> Address {
+        // only the canonical form of a signature is accepted (low `s`, `v` of 27 or 28),
+        // so every permit has exactly one valid signature
+        if U256::from_be_bytes(s.0) > SECP256K1N_HALF || (v != 27 && v != 28) {
+            return Address::ZERO;
+        }
+
+        let input: Vec<u8> = [
+            digest.as_slice(),
+            U256::from(v).to_be_bytes::<32>().as_slice(),
+            r.as_slice(),
+            s.as_slice(),
+        ].concat();
+
+        match static_call(Call::new(), ECRECOVER, &input) {
+            Ok(output) if output.len() == 32 => Address::from_slice(&output[12..]),
+            _ => Address::ZERO,
+        }
+    }
+
     /// Movement of funds between 2 accounts
     /// (invoked by the external transfer() and transfer_from() functions )
     pub fn _transfer(&mut self, from: Address, to: Address, value: U256) -> Result<(), Erc20Error> {
         // Decreasing sender balance
-        let mut sender_balance = self.balances.setter(from);
-        let old_sender_balance = sender_balance.get();
+        let old_sender_balance = self.balances.get(from);
         if old_sender_balance < value {
             return Err(
                 Erc20Error::InsufficientBalance(InsufficientBalance {
@@ -304,35 +1407,59 @@ impl Erc20 {
                 })
             );
         }
-        sender_balance.set(old_sender_balance - value);
+        // locked tokens can only be paid into a platform contract itself (ticket escrow, safe vote, patronage);
+        // paid to any other account, even by a platform contract, they would come out unlocked
+        let platform_spend = self.platform.get(to);
+        let locked_spent = self.spend_locked(from, old_sender_balance, value, platform_spend)?;
+        if locked_spent != U256::ZERO {
+            // kept so the platform can put the tokens back under the lock if it refunds them
+            let mut platform_locked = self.platform_locked.setter(to);
+            let mut paid_in = platform_locked.setter(from);
+            let old_paid_in = paid_in.get();
+            paid_in.set(old_paid_in + locked_spent);
+        }
+        self.balances.setter(from).set(old_sender_balance - value);
 
         // Increasing receiver balance
         let mut to_balance = self.balances.setter(to);
         let new_to_balance = to_balance.get() + value;
         to_balance.set(new_to_balance);
 
+        // Moving the voting power along with the tokens
+        self.move_votes(self.delegates.get(from), self.delegates.get(to), value);
+
         // Emitting the transfer event
-        evm::log(Transfer { from, to, value, time: block::timestamp() as u64 });
+        Self::log_transfer(from, to, value);
         Ok(())
     }
 
     /// Mints `value` tokens to `address`
     pub fn _mint(&mut self, address: Address, value: U256) -> Result<(), Erc20Error> {
+        // the supply can never go above the cap once it is set
+        let max_supply = self.max_supply.get();
+        let total_supply = self.total_supply.get();
+        if max_supply != U256::ZERO && total_supply + value > max_supply {
+            return Err(
+                Erc20Error::MaxSupplyExceeded(MaxSupplyExceeded {
+                    max_supply,
+                    total_supply,
+                    amount: value,
+                })
+            );
+        }
+
         // Increasing balance
         let mut balance = self.balances.setter(address);
         let new_balance = balance.get() + value;
         balance.set(new_balance);
 
         // Increasing total supply
-        self.total_supply.set(self.total_supply.get() + value);
+        self.total_supply.set(total_supply + value);
+        self.supply_checkpoints.write(total_supply + value);
+        self.move_votes(Address::ZERO, self.delegates.get(address), value);
 
         // Emitting the transfer event
-        evm::log(Transfer {
-            from: Address::ZERO,
-            to: address,
-            value,
-            time: block::timestamp() as u64,
-        });
+        Self::log_transfer(Address::ZERO, address, value);
 
         Ok(())
     }
@@ -340,8 +1467,7 @@ impl Erc20 {
     /// Burns `value` tokens from `address`
     pub fn _burn(&mut self, address: Address, value: U256) -> Result<(), Erc20Error> {
         // Decreasing balance
-        let mut balance = self.balances.setter(address);
-        let old_balance = balance.get();
+        let old_balance = self.balances.get(address);
         if old_balance < value {
             return Err(
                 Erc20Error::InsufficientBalance(InsufficientBalance {
@@ -351,22 +1477,347 @@ impl Erc20 {
                 })
             );
         }
-        balance.set(old_balance - value);
+        self.spend_locked(address, old_balance, value, false)?;
+        self.balances.setter(address).set(old_balance - value);
 
         // Decreasing the total supply
-        self.total_supply.set(self.total_supply.get() - value);
+        let total_supply = self.total_supply.get() - value;
+        self.total_supply.set(total_supply);
+        self.supply_checkpoints.write(total_supply);
+        self.move_votes(self.delegates.get(address), Address::ZERO, value);
 
         // Emitting the transfer event
-        evm::log(Transfer {
-            from: address,
-            to: Address::ZERO,
+        Self::log_transfer(address, Address::ZERO, value);
+
+        Ok(())
+    }
+
+    /// Emits the standard `Transfer` event and its timestamped NovaVault twin
+    pub fn log_transfer(from: Address, to: Address, value: U256) {
+        evm::log(Transfer { from, to, value });
+        evm::log(NovaTransfer {
+            from,
+            to,
             value,
             time: block::timestamp() as u64,
         });
+    }
+
+    /// Merkle root rebuilt from `leaf` and its `proof`, hashing each pair in sorted order
+    pub fn proof_root(leaf: B256, proof: &[B256]) -> B256 {
+        proof.iter().fold(leaf, |computed, node| {
+            if computed <= *node {
+                keccak([computed.as_slice(), node.as_slice()].concat())
+            } else {
+                keccak([node.as_slice(), computed.as_slice()].concat())
+            }
+        })
+    }
+
+    /// Moves `amount` of voting power from the `from` delegate to the `to` delegate
+    pub fn move_votes(&mut self, from: Address, to: Address, amount: U256) {
+        if from == to || amount == U256::ZERO {
+            return;
+        }
+
+        if from != Address::ZERO {
+            let mut checkpoints = self.vote_checkpoints.setter(from);
+            let previous_votes = checkpoints.latest();
+            let new_votes = previous_votes.saturating_sub(amount);
+            checkpoints.write(new_votes);
+            evm::log(DelegateVotesChanged { delegate: from, previous_votes, new_votes });
+        }
+
+        if to != Address::ZERO {
+            let mut checkpoints = self.vote_checkpoints.setter(to);
+            let previous_votes = checkpoints.latest();
+            let new_votes = previous_votes + amount;
+            checkpoints.write(new_votes);
+            evm::log(DelegateVotesChanged { delegate: to, previous_votes, new_votes });
+        }
+    }
+
+    /// Snapshots can only be read for blocks that are already over
+    pub fn check_past(block: u64) -> Result<(), Erc20Error> {
+        let current = block::number();
+        if block >= current {
+            return Err(Erc20Error::FutureLookup(FutureLookup { block, current }));
+        }
+        Ok(())
+    }
+
+    /// Makes sure `value` out of `balance` only touches vested tokens of `from`;
+    /// locked tokens can only be spent when `platform_spend` is set, i.e. paid to a platform contract.
+    /// Returns how many locked tokens were spent
+    pub fn spend_locked(
+        &mut self,
+        from: Address,
+        balance: U256,
+        value: U256,
+        platform_spend: bool
+    ) -> Result<U256, Erc20Error> {
+        let free = balance.saturating_sub(self.locked_balance_of(from));
+        if value <= free {
+            return Ok(U256::ZERO);
+        }
+
+        if !platform_spend {
+            return Err(
+                Erc20Error::LockedBalance(LockedBalance {
+                    from,
+                    free,
+                    want: value,
+                })
+            );
+        }
+
+        // locked tokens spent on the platform come out of the unvested part of the grant
+        let mut lock = self.locks.setter(from);
+        let used = lock.used.get();
+        lock.used.set(used + (value - free));
+        Ok(value - free)
+    }
 
+    /// Quota `minter` has now, and the time its refill is counted from.
+    /// The refill only moves `updated` forward by the time the refilled tokens took,
+    /// so the fraction of a token earned since then is kept for the next mint
+    pub fn refilled_quota(&self, minter: Address) -> (U256, u64) {
+        let quota = self.mint_quotas.getter(minter);
+        let capacity = quota.capacity.get();
+        let updated = quota.updated.get().to::<u64>();
+        if capacity == U256::ZERO {
+            return (U256::MAX, updated);
+        }
+
+        let rate = quota.rate.get();
+        let period = quota.period.get().to::<u64>();
+        if period == 0 || rate == U256::ZERO {
+            return (quota.remaining.get(), updated);
+        }
+
+        let now = block::timestamp();
+        let elapsed = now.saturating_sub(updated);
+        let refill = rate.saturating_mul(U256::from(elapsed)) / U256::from(period);
+        let available = quota.remaining.get().saturating_add(refill);
+        if available >= capacity {
+            return (capacity, now);
+        }
+
+        let spent = refill.saturating_mul(U256::from(period)) / rate;
+        (available, updated + spent.to::<u64>().min(elapsed))
+    }
+
+    /// Takes `amount` out of `minter`'s quota, failing when it is not available
+    pub fn use_quota(&mut self, minter: Address, amount: U256) -> Result<(), Erc20Error> {
+        if self.mint_quotas.getter(minter).capacity.get() == U256::ZERO {
+            return Ok(());
+        }
+
+        let (available, updated) = self.refilled_quota(minter);
+        if amount > available {
+            return Err(
+                Erc20Error::MintQuotaExceeded(MintQuotaExceeded {
+                    minter,
+                    available,
+                    amount,
+                })
+            );
+        }
+
+        let mut quota = self.mint_quotas.setter(minter);
+        quota.remaining.set(available - amount);
+        quota.updated.set(U64::from(updated));
+
+        evm::log(MintQuotaUsed {
+            minter,
+            amount,
+            remaining: available - amount,
+            time: block::timestamp() as u64,
+        });
         Ok(())
     }
 
+    /// Mints `amount` sold tokens to the buyer, forwards `cost` to the admin
+    /// and refunds what is left of `value`
+    pub fn fill_buy(
+        &mut self,
+        amount: U256,
+        cost: U256,
+        value: U256,
+        price: U256
+    ) -> Result<(), Erc20Error> {
+        let market = self.market.get();
+        if amount > market {
+            return Err(
+                Erc20Error::MarketExceeded(MarketExceeded {
+                    from: msg::sender(),
+                    market,
+                    amount,
+                })
+            );
+        }
+
+        self.market.set(market - amount);
+        self.sold.set(self.sold.get() + amount);
+
+        self._mint(msg::sender(), amount)?;
+
+        // part of the payment stays in the contract to back sell-backs
+        let kept = (cost * self.reserve_bps.get()) / U256::from(BPS);
+        self.reserve.set(self.reserve.get() + kept);
+
+        self.send_proceeds(cost - kept)?;
+
+        if value > cost {
+            call(Call::new_in(self).value(value - cost), msg::sender(), &[]).map_err(|_e| {
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 12,
+                })
+            })?;
+        }
+
+        evm::log(TokenSold {
+            buyer: msg::sender(),
+            price,
+            value: cost,
+            market,
+            time: block::timestamp() as u64,
+        });
+
+        Ok(())
+    }
+
+    /// Sends `value` wei of sale proceeds to the treasury contract, or to the admin while none is set
+    pub fn send_proceeds(&mut self, value: U256) -> Result<(), Erc20Error> {
+        if value == U256::ZERO {
+            return Ok(());
+        }
+
+        let treasury = self.treasury.get();
+        if treasury == Address::ZERO {
+            let admin = self.admin.get();
+            call(Call::new_in(self).value(value), admin, &[]).map_err(|_e| {
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 11,
+                })
+            })?;
+            return Ok(());
+        }
+
+        ITreasury::new(treasury)
+            .deposit(Call::new_in(self).value(value))
+            .map_err(|_e| {
+                Erc20Error::InvalidParameter(InvalidParameter {
+                    point: 11,
+                })
+            })
+    }
+
+    /// Cost in wei of `amount` token units once `sold` units have been sold
+    pub fn cost_of(&self, sold: U256, amount: U256) -> Result<U256, Erc20Error> {
+        let overflow = || Erc20Error::InvalidParameter(InvalidParameter { point: 21 });
+        match self.curve_mode.get().to::<u8>() {
+            CURVE_LINEAR => {
+                // area under base + slope * x / unit between sold and sold + amount
+                let unit = U256::from(10).pow(U256::from(NovaParams::DECIMALS));
+                let span = sold
+                    .checked_mul(U256::from(2))
+                    .and_then(|x| x.checked_add(amount))
+                    .and_then(|x| x.checked_mul(amount))
+                    .and_then(|x| x.checked_mul(self.curve_slope.get()))
+                    .ok_or_else(overflow)?;
+                self.curve_base
+                    .get()
+                    .checked_mul(amount)
+                    .and_then(|x| x.checked_add(span / (unit * U256::from(2))))
+                    .ok_or_else(overflow)
+            }
+            CURVE_EXPONENTIAL => self.exp_cost(sold, amount).ok_or_else(overflow),
+            CURVE_ORACLE => self.oracle_price().checked_mul(amount).ok_or_else(overflow),
+            _ => self.price.get().checked_mul(amount).ok_or_else(overflow),
+        }
+    }
+
+    /// Wei per token unit from the oracle, or the manual `price` when the oracle is unset or its feed is stale
+    pub fn oracle_price(&self) -> U256 {
+        let oracle = self.oracle.get();
+        if oracle == Address::ZERO {
+            return self.price.get();
+        }
+        IOracle::new(oracle).unit_price(Call::new()).unwrap_or_else(|_e| self.price.get())
+    }
+
+    /// Exponential curve cost; the unit price within step `k` is `base * (1 + slope / BPS)^k`
+    pub fn exp_cost(&self, sold: U256, amount: U256) -> Option<U256> {
+        let base = self.curve_base.get();
+        let growth = self.curve_slope.get();
+        let step = self.curve_step.get();
+        let end = sold.checked_add(amount)?;
+        let (first, last) = (sold / step, end / step);
+
+        let first_price = base.checked_mul(Self::growth_pow(growth, first)?)? / WAD;
+        if first == last {
+            return first_price.checked_mul(amount);
+        }
+
+        // what is left of the first step, the full steps in between and the start of the last step
+        let next = first + U256::from(1);
+        let head = first_price.checked_mul(next * step - sold)?;
+        let q_next = Self::growth_pow(growth, next)?;
+        let q_last = Self::growth_pow(growth, last)?;
+        let body = if growth == U256::ZERO {
+            base.checked_mul(step)?.checked_mul(last - next)?
+        } else {
+            // geometric series: base * step * (q^last - q^next) / (q - 1)
+            let series = (q_last - q_next).checked_mul(U256::from(BPS))? / growth;
+            series.checked_mul(base)?.checked_mul(step)? / WAD
+        };
+        let tail = (base.checked_mul(q_last)? / WAD).checked_mul(end - last * step)?;
+
+        head.checked_add(body)?.checked_add(tail)
+    }
+
+    /// (1 + growth / BPS)^k in WAD precision
+    pub fn growth_pow(growth: U256, k: U256) -> Option<U256> {
+        let mut factor = WAD.checked_mul(growth.checked_add(U256::from(BPS))?)? / U256::from(BPS);
+        let mut result = WAD;
+        let mut k = k;
+        while k > U256::ZERO {
+            if k.bit(0) {
+                result = result.checked_mul(factor)? / WAD;
+            }
+            k >>= 1;
+            if k > U256::ZERO {
+                factor = factor.checked_mul(factor)? / WAD;
+            }
+        }
+        Some(result)
+    }
+
+    /// Largest amount of token units `value` wei can pay for at the current point of the curve
+    pub fn affordable(&self, value: U256, price: U256) -> U256 {
+        let mode = self.curve_mode.get().to::<u8>();
+        if mode == CURVE_FLAT || mode == CURVE_ORACLE {
+            return value / price;
+        }
+
+        // the price never goes down, so value / price is an upper bound
+        let (mut low, mut high) = (U256::ZERO, value / price);
+        let sold = self.sold.get();
+        while low < high {
+            let mid = (low + high + U256::from(1)) / U256::from(2);
+            match self.cost_of(sold, mid) {
+                Ok(cost) if cost <= value => {
+                    low = mid;
+                }
+                _ => {
+                    high = mid - U256::from(1);
+                }
+            }
+        }
+        low
+    }
+
     // this is the lock that lockes a user as the admin of this contract; there by making sure that it can only be called once
     pub fn check_admin(&mut self) -> Result<(), Erc20Error> {
         let default_x = Address::from([0x00; 20]);
@@ -383,3 +1834,49 @@ impl Erc20 {
         Ok(())
     }
 }
+
+impl Checkpoints {
+    /// Latest recorded value
+    pub fn latest(&self) -> U256 {
+        match self.values.len() {
+            0 => U256::ZERO,
+            len => self.values.get(len - 1).unwrap_or_default(),
+        }
+    }
+
+    /// Value recorded at the end of `block`
+    pub fn upper_lookup(&self, block: u64) -> U256 {
+        let (mut low, mut high) = (0, self.blocks.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.blocks.get(mid).unwrap_or_default().to::<u64>() > block {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        if high == 0 {
+            return U256::ZERO;
+        }
+        self.values.get(high - 1).unwrap_or_default()
+    }
+
+    /// Records `value` for the current block, overwriting an earlier change in the same block
+    pub fn write(&mut self, value: U256) {
+        let current = block::number();
+        let len = self.blocks.len();
+        if len > 0 && self.blocks.get(len - 1).unwrap_or_default().to::<u64>() == current {
+            if let Some(mut last) = self.values.setter(len - 1) {
+                last.set(value);
+            }
+            return;
+        }
+
+        self.blocks.push(U64::from(current));
+        self.values.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests;
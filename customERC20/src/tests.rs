@@ -0,0 +1,428 @@
+//! Unit tests run the contract against an in-memory stand-in of the Stylus host:
+//! storage lives in a per-thread map, the caller, block and contract address
+//! are the fixed values below, and the only other contract is an ERC-1363
+//! receiver at `RECEIVER` that accepts every hook and keeps its calldata.
+
+use super::*;
+use alloy_sol_types::SolValue;
+
+// the account every call is made from; it becomes the admin on its first admin call
+const SENDER: Address = Address::new([0x11; 20]);
+const TOKEN: Address = Address::new([0x22; 20]);
+const RECEIVER: Address = Address::new([0x33; 20]);
+const NOW: u64 = 1_700_000_000;
+const BLOCK: u64 = 1_000;
+
+mod host {
+    use super::{ Address, BLOCK, NOW, RECEIVER, SENDER, TOKEN };
+    use sha3::{ Digest, Keccak256 };
+    use std::{ cell::RefCell, collections::HashMap };
+
+    thread_local! {
+        static STORAGE: RefCell<HashMap<[u8; 32], [u8; 32]>> = RefCell::new(HashMap::new());
+        static HOOKED: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+        static RETURNED: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    // calldata of the last hook the receiver ran
+    pub fn hooked() -> Vec<u8> {
+        HOOKED.with(|h| h.borrow().clone())
+    }
+
+    unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+    }
+
+    unsafe fn address(dest: *mut u8, address: Address) {
+        write(dest, address.as_slice());
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = STORAGE.with(|s| s.borrow().get(&key).copied().unwrap_or_default());
+        write(dest, &value);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = *(value as *const [u8; 32]);
+        STORAGE.with(|s| s.borrow_mut().insert(key, value));
+    }
+
+    #[no_mangle]
+    extern "C" fn storage_flush_cache(_clear: bool) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+        let input = std::slice::from_raw_parts(bytes, len);
+        write(output, &Keccak256::digest(input));
+    }
+
+    #[no_mangle]
+    extern "C" fn emit_log(_data: *const u8, _len: usize, _topics: usize) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        address(sender, SENDER);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_value(value: *mut u8) {
+        write(value, &[0; 32]);
+    }
+
+    #[no_mangle]
+    extern "C" fn msg_reentrant() -> bool {
+        false
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn contract_address(dest: *mut u8) {
+        address(dest, TOKEN);
+    }
+
+    #[no_mangle]
+    extern "C" fn block_timestamp() -> u64 {
+        NOW
+    }
+
+    #[no_mangle]
+    extern "C" fn block_number() -> u64 {
+        BLOCK
+    }
+
+    #[no_mangle]
+    extern "C" fn chainid() -> u64 {
+        42161
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn account_code_size(address: *const u8) -> usize {
+        (std::slice::from_raw_parts(address, 20) == RECEIVER.as_slice()).into()
+    }
+
+    // only the receiver has code
+    #[no_mangle]
+    unsafe extern "C" fn account_codehash(address: *const u8, dest: *mut u8) {
+        let has_code = std::slice::from_raw_parts(address, 20) == RECEIVER.as_slice();
+        write(dest, &[has_code.into(); 32]);
+    }
+
+    // the receiver answers every hook with its own selector; any other call reverts
+    #[no_mangle]
+    unsafe extern "C" fn call_contract(
+        contract: *const u8,
+        calldata: *const u8,
+        calldata_len: usize,
+        _value: *const u8,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        if std::slice::from_raw_parts(contract, 20) != RECEIVER.as_slice() {
+            *return_data_len = 0;
+            return 1;
+        }
+
+        let calldata = std::slice::from_raw_parts(calldata, calldata_len);
+        HOOKED.with(|h| *h.borrow_mut() = calldata.to_vec());
+        let mut returned = [0u8; 32];
+        returned[..4].copy_from_slice(&calldata[..4]);
+        RETURNED.with(|r| *r.borrow_mut() = returned.to_vec());
+        *return_data_len = 32;
+        0
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn delegate_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn static_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn read_return_data(dest: *mut u8, offset: usize, size: usize) -> usize {
+        RETURNED.with(|r| {
+            let returned = r.borrow();
+            let returned = returned.get(offset..).unwrap_or_default();
+            let size = size.min(returned.len());
+            write(dest, &returned[..size]);
+            size
+        })
+    }
+
+    #[no_mangle]
+    extern "C" fn return_data_size() -> usize {
+        RETURNED.with(|r| r.borrow().len())
+    }
+}
+
+// a fresh token; storage is empty on every test thread
+fn token() -> Erc20 {
+    unsafe { <Erc20 as stylus_sdk::storage::StorageType>::new(U256::ZERO, 0) }
+}
+
+fn ok<T>(result: Result<T, Erc20Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("call reverted: 0x{}", alloy_primitives::hex::encode(Vec::<u8>::from(e))),
+    }
+}
+
+fn account(byte: u8) -> Address {
+    Address::new([byte; 20])
+}
+
+fn tokens(amount: u64) -> U256 {
+    U256::from(amount)
+}
+
+#[test]
+fn locked_tokens_can_not_be_laundered_through_a_platform_transfer() {
+    let mut token = token();
+    let (airdropped, alt) = (account(0xa1), account(0xa2));
+
+    // SENDER plays the market: a platform contract moving tokens with an allowance
+    ok(token.set_mint(vec![SENDER], vec![true]));
+    ok(token.set_platform(vec![SENDER], vec![true]));
+    ok(token.mint_locked(airdropped, tokens(1_000), 0, 90 * 86_400));
+    token._approve(airdropped, SENDER, tokens(1_000));
+
+    // a "purchase" paying the locked grant to another account of the same user
+    let laundered = token.transfer_from(airdropped, alt, tokens(400));
+    assert!(matches!(laundered, Err(Erc20Error::LockedBalance(_))));
+    assert_eq!(token.balance_of(alt), U256::ZERO);
+    assert_eq!(token.locked_balance_of(airdropped), tokens(1_000));
+
+    // paying into the platform contract itself is still allowed and uses up the grant
+    assert!(ok(token.transfer_from(airdropped, SENDER, tokens(400))));
+    assert_eq!(token.locked_balance_of(airdropped), tokens(600));
+    assert_eq!(token.balance_of(SENDER), tokens(400));
+}
+
+#[test]
+fn refunded_locked_tokens_go_back_under_the_lock() {
+    let mut token = token();
+    let buyer = account(0xa3);
+
+    // SENDER plays the ticket escrow
+    ok(token.set_mint(vec![SENDER], vec![true]));
+    ok(token.set_platform(vec![SENDER], vec![true]));
+    ok(token.mint_locked(buyer, tokens(1_000), 0, 90 * 86_400));
+    token._approve(buyer, SENDER, tokens(1_000));
+    assert!(ok(token.transfer_from(buyer, SENDER, tokens(400))));
+    assert_eq!(token.platform_locked_of(SENDER, buyer), tokens(400));
+
+    // the platform can not relock more than was paid in locked
+    assert_eq!(ok(token.refund_locked(buyer, tokens(300), tokens(500))), tokens(300));
+    assert_eq!(token.locked_balance_of(buyer), tokens(900));
+    assert_eq!(ok(token.refund_locked(buyer, tokens(100), U256::ZERO)), U256::ZERO);
+    assert_eq!(token.locked_balance_of(buyer), tokens(900));
+    assert_eq!(token.platform_locked_of(SENDER, buyer), tokens(100));
+    assert_eq!(token.balance_of(buyer), tokens(1_000));
+}
+
+#[test]
+fn transfer_and_call_tells_a_platform_the_locked_part_of_the_payment() {
+    let mut token = token();
+    ok(token.set_mint(vec![SENDER], vec![true]));
+    ok(token.set_platform(vec![RECEIVER], vec![true]));
+    ok(token._mint(SENDER, tokens(100)));
+    ok(token.mint_locked(SENDER, tokens(1_000), 0, 90 * 86_400));
+
+    let data = U256::from(7).to_be_bytes::<32>().to_vec();
+    assert!(ok(token.transfer_and_call(RECEIVER, tokens(300), data.into())));
+    assert_eq!(token.platform_locked_of(RECEIVER, SENDER), tokens(200));
+
+    // the hook was selected correctly and got the caller's data with the locked part appended
+    let hooked = host::hooked();
+    assert_eq!(hooked[..4], ON_TRANSFER_RECEIVED);
+    let (_operator, from, value, data) = <(Address, Address, U256, alloy_primitives::Bytes)>::abi_decode_params(
+        &hooked[4..],
+        true
+    ).unwrap();
+    assert_eq!((from, value), (SENDER, tokens(300)));
+    assert_eq!(data.len(), 64);
+    assert_eq!(U256::from_be_slice(&data[..32]), U256::from(7));
+    assert_eq!(U256::from_be_slice(&data[32..]), tokens(200));
+}
+
+#[test]
+fn a_failed_proceeds_forward_reverts_the_sale() {
+    let mut token = token();
+    ok(token.check_admin());
+    // the admin is not the receiver, so sending it ETH fails in this host
+    let sent = token.send_proceeds(U256::from(100));
+    assert!(matches!(sent, Err(Erc20Error::InvalidParameter(InvalidParameter { point: 11 }))));
+    assert!(token.send_proceeds(U256::ZERO).is_ok());
+}
+
+fn curve(mode: u8, base: u64, slope: u64, step: u64) -> Erc20 {
+    let mut token = token();
+    token.curve_mode.set(U8::from(mode));
+    token.curve_base.set(tokens(base));
+    token.curve_slope.set(tokens(slope));
+    token.curve_step.set(tokens(step));
+    token
+}
+
+#[test]
+fn flat_price_is_charged_per_unit() {
+    let mut token = curve(CURVE_FLAT, 0, 0, 0);
+    token.price.set(tokens(7));
+    assert_eq!(ok(token.cost_of(tokens(5_000), tokens(300))), tokens(2_100));
+}
+
+#[test]
+fn linear_curve_charges_the_area_under_the_price() {
+    // 100 wei per unit, plus 1 wei per unit for every whole token (10^10 units) sold
+    let unit = 10_000_000_000;
+    let token = curve(CURVE_LINEAR, 100, 1, 0);
+    assert_eq!(ok(token.cost_of(U256::ZERO, tokens(2 * unit))), tokens(200 * unit + 2 * unit));
+    assert_eq!(ok(token.cost_of(tokens(2 * unit), tokens(unit))), tokens(100 * unit + unit * 5 / 2));
+}
+
+#[test]
+fn exponential_curve_sums_every_step() {
+    // 1_000 wei per unit, 10% more every 100 units
+    let token = curve(CURVE_EXPONENTIAL, 1_000, 1_000, 100);
+    assert_eq!(token.exp_cost(U256::ZERO, tokens(100)), Some(tokens(100_000)));
+    assert_eq!(token.exp_cost(tokens(120), tokens(30)), Some(tokens(33_000)));
+    // 100 * 1_000 + 100 * 1_100 + 50 * 1_210
+    assert_eq!(ok(token.cost_of(U256::ZERO, tokens(250))), tokens(270_500));
+    // 80 * 1_000 + 100 * 1_100 + 10 * 1_210
+    assert_eq!(ok(token.cost_of(tokens(20), tokens(190))), tokens(202_100));
+}
+
+#[test]
+fn exponential_curve_without_growth_is_flat() {
+    let token = curve(CURVE_EXPONENTIAL, 1_000, 0, 100);
+    assert_eq!(token.exp_cost(tokens(50), tokens(250)), Some(tokens(250_000)));
+}
+
+#[test]
+fn curve_costs_revert_instead_of_wrapping() {
+    let mut token = curve(CURVE_LINEAR, 100, 0, 0);
+    token.curve_slope.set(U256::MAX);
+    let cost = token.cost_of(U256::ZERO, tokens(2));
+    assert!(matches!(cost, Err(Erc20Error::InvalidParameter(InvalidParameter { point: 21 }))));
+
+    let token = curve(CURVE_EXPONENTIAL, 1_000, 1_000, 1);
+    assert_eq!(token.exp_cost(U256::ZERO, tokens(1_000_000)), None);
+
+    // a cost that fits can still overflow once the sell spread is applied
+    let mut token = curve(CURVE_FLAT, 0, 0, 0);
+    token.price.set(U256::MAX / U256::from(2));
+    token.sold.set(U256::from(1));
+    let value = token.quote_sell(U256::from(1));
+    assert!(matches!(value, Err(Erc20Error::InvalidParameter(InvalidParameter { point: 21 }))));
+}
+
+#[test]
+fn minters_without_a_quota_are_not_limited() {
+    let mut token = token();
+    let registration = account(0xb1);
+    assert_eq!(token.mint_quota_of(registration), U256::MAX);
+    ok(token.use_quota(registration, tokens(1_000_000)));
+    assert_eq!(token.mint_quota_of(registration), U256::MAX);
+
+    // an existing minter keeps minting locked grants after the upgrade
+    ok(token.set_mint(vec![SENDER], vec![true]));
+    ok(token.mint_locked(account(0xb2), tokens(500), 7 * 86_400, 90 * 86_400));
+    assert_eq!(token.balance_of(account(0xb2)), tokens(500));
+}
+
+// a quota of 100 refilling by 10 every minute, last used `ago` seconds ago with `remaining` left
+fn quota(remaining: u64, ago: u64) -> Erc20 {
+    let mut token = token();
+    let mut quota = token.mint_quotas.setter(SENDER);
+    quota.capacity.set(tokens(100));
+    quota.rate.set(tokens(10));
+    quota.period.set(U64::from(60));
+    quota.remaining.set(tokens(remaining));
+    quota.updated.set(U64::from(NOW - ago));
+    token
+}
+
+#[test]
+fn mint_quota_refills_over_time_up_to_capacity() {
+    // 10 * 90 / 60 = 15 refilled
+    assert_eq!(quota(5, 90).mint_quota_of(SENDER), tokens(20));
+    assert_eq!(quota(5, 0).mint_quota_of(SENDER), tokens(5));
+    assert_eq!(quota(95, 3_600).mint_quota_of(SENDER), tokens(100));
+}
+
+#[test]
+fn using_a_quota_keeps_the_partial_refill() {
+    // 100 seconds refill 16 tokens and 4 seconds towards the next one
+    let mut token = quota(5, 100);
+    ok(token.use_quota(SENDER, tokens(21)));
+    assert_eq!(token.mint_quotas.getter(SENDER).remaining.get(), U256::ZERO);
+    assert_eq!(token.mint_quotas.getter(SENDER).updated.get(), U64::from(NOW - 4));
+
+    let exceeded = token.use_quota(SENDER, tokens(1));
+    assert!(matches!(exceeded, Err(Erc20Error::MintQuotaExceeded(_))));
+
+    // a full quota restarts its refill from now
+    let mut token = quota(95, 3_600);
+    ok(token.use_quota(SENDER, tokens(40)));
+    assert_eq!(token.mint_quota_of(SENDER), tokens(60));
+    assert_eq!(token.mint_quotas.getter(SENDER).updated.get(), U64::from(NOW));
+}
+
+// a grant of 1_000 vesting over 1_000 seconds after a 100 second cliff, started `ago` seconds ago
+fn grant(ago: u64, used: u64) -> (Erc20, Address) {
+    let mut token = token();
+    let holder = account(0xc1);
+    let mut lock = token.locks.setter(holder);
+    lock.total.set(tokens(1_000));
+    lock.used.set(tokens(used));
+    lock.start.set(U64::from(NOW - ago));
+    lock.cliff.set(U64::from(100));
+    lock.duration.set(U64::from(1_000));
+    (token, holder)
+}
+
+#[test]
+fn grants_vest_linearly_after_the_cliff() {
+    let (token, holder) = grant(99, 0);
+    assert_eq!(token.vested_of(holder), U256::ZERO);
+    assert_eq!(token.locked_balance_of(holder), tokens(1_000));
+
+    let (token, holder) = grant(250, 0);
+    assert_eq!(token.vested_of(holder), tokens(250));
+    assert_eq!(token.locked_balance_of(holder), tokens(750));
+
+    let (token, holder) = grant(5_000, 0);
+    assert_eq!(token.vested_of(holder), tokens(1_000));
+    assert_eq!(token.locked_balance_of(holder), U256::ZERO);
+
+    assert_eq!(token.vested_of(account(0xc2)), U256::ZERO);
+}
+
+#[test]
+fn locked_tokens_spent_on_the_platform_are_no_longer_locked() {
+    let (token, holder) = grant(250, 300);
+    assert_eq!(token.locked_balance_of(holder), tokens(450));
+
+    let (token, holder) = grant(900, 300);
+    assert_eq!(token.locked_balance_of(holder), U256::ZERO);
+}
@@ -1,11 +1,33 @@
 #![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
 extern crate alloc;
 
-use alloy_primitives::{ Address, U256, U32 };
+use alloc::{ format, string::String };
+use alloy_primitives::{ Address, FixedBytes, U256, U64 };
 use alloy_sol_types::sol;
-use stylus_sdk::{ evm, msg, block, prelude::* };
+use stylus_sdk::{ abi::Bytes, block, evm, msg, prelude::*, call::Call, types::AddressVM };
 use core::marker::PhantomData;
 
+// ERC-1155 receivers must return their own selector to accept tokens
+const ON_ERC1155_RECEIVED: [u8; 4] = [0xf2, 0x3a, 0x6e, 0x61];
+const ON_ERC1155_BATCH_RECEIVED: [u8; 4] = [0xbc, 0x19, 0x7c, 0x81];
+
+// redeemers must return this selector to grant the perk of a redeemed token
+const ON_REDEEM: [u8; 4] = [0x99, 0xe4, 0x21, 0x2d];
+
+// ERC-165 interface ids
+const IERC165_ID: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+const IERC1155_ID: [u8; 4] = [0xd9, 0xb6, 0x7a, 0x26];
+const IERC1155_METADATA_URI_ID: [u8; 4] = [0x0e, 0x89, 0x34, 0x1c];
+const IERC2981_ID: [u8; 4] = [0x2a, 0x55, 0x20, 0x5a];
+
+// royalties are expressed in basis points of the sale price
+const BPS: u64 = 10_000;
+
+// soulbound badges; a badge id is 1 << BADGE_BIT | kind << 128 | gallery id, so it never meets an SFT id
+const BADGE_BIT: usize = 255;
+const VOTER_BADGE: u8 = 1; // top-3 voter in the gallery
+const CREATOR_BADGE: u8 = 2; // accepted creator in the gallery
+
 pub trait Erc1155Params {
     /// Immutable Collection name
     const COLLECTIONNAME: &'static str;
@@ -18,12 +40,36 @@ impl Erc1155Params for NovaParams {
 
 // ERC-1155 Events
 sol! {
-    event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value, uint32 time );
-    event TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values, uint32 time);
-    event ApprovalForAll(address indexed account, address indexed operator, bool approved, uint32 time);
+    event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value);
+    event TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values);
+    event ApprovalForAll(address indexed account, address indexed operator, bool approved);
+    event Approval(address indexed account, address indexed operator, uint256 indexed id, uint256 amount, uint64 expiry);
+    event ApprovalRevoked(address indexed account, address indexed operator, uint256 indexed id);
     event URI(string value, uint256 indexed id);
+    event RoyaltySet(uint256 indexed id, address indexed receiver, uint256 bps);
+    event SetRedeemer(address indexed redeemer, bool state);
+    event Redeemed(address indexed holder, address indexed redeemer, uint256 indexed id, uint256 amount, uint256 redemptions);
+    event MaxSupplySet(uint256 indexed id, uint256 max_supply);
+    event SetBadgeIssuer(address indexed issuer, bool state);
+    event BadgeMinted(address indexed to, uint256 indexed id, uint8 kind, uint256 indexed gallery_id);
 
     error InvalidParameter(uint8 point);
+    error ReceiverRejected(address receiver);
+    error MaxSupplyExceeded(uint256 id, uint256 max_supply, uint256 minted);
+    error Soulbound(uint256 id);
+}
+
+sol_interface! {
+    // contracts receiving Nova tokens must implement these hooks
+    interface IErc1155Receiver {
+        function onERC1155Received(address operator, address from, uint256 id, uint256 value, bytes calldata data) external returns (bytes4);
+        function onERC1155BatchReceived(address operator, address from, uint256[] calldata ids, uint256[] calldata values, bytes calldata data) external returns (bytes4);
+    }
+
+    // contracts granting a perk, e.g. a free ticket, for burned tokens
+    interface IRedeemer {
+        function onRedeem(address holder, uint256 id, uint256 amount, address creator, bytes calldata data) external returns (bytes4);
+    }
 }
 
 sol_storage! {
@@ -34,10 +80,36 @@ sol_storage! {
         mapping(uint256 => mapping(address => uint256)) balances;
         // Approval map: owner -> operator -> approval status
         mapping(address => mapping(address => bool)) operator_approvals;
+        // Per token approvals: owner -> operator -> token ID -> amount the operator may still move
+        mapping(address => mapping(address => mapping(uint256 => TokenApproval))) token_approvals;
 
         // total_supply mapping: token ID -> totalsupply
         mapping(uint256 => uint256) total_supply;
-        // mapping( uint256 => string) uris;
+        // token ID -> edition cap, set once by the minter; zero means uncapped
+        mapping(uint256 => uint256) max_supply;
+        // token ID -> tokens ever minted; burning does not free up the cap
+        mapping(uint256 => uint256) minted;
+        // uri of each token; set by the admin or fixed when its metadata is frozen
+        mapping( uint256 => string) uris;
+        // template used for tokens without their own uri, e.g. "https://novavault.app/api/{id}.json";
+        // when empty the metadata is served on-chain as a data-URI JSON
+        string base_uri;
+        // token ID -> metadata can no longer change
+        mapping(uint256 => bool) frozen;
+        // token ID -> ERC-2981 royalty, set by the minter when the token is first minted
+        mapping(uint256 => Royalty) royalties;
+        // contracts tokens can be redeemed with
+        mapping(address => bool) redeemers;
+        // token ID -> tokens redeemed so far
+        mapping(uint256 => uint256) redemptions;
+        // token ID -> the token is a badge that can not be transferred
+        mapping(uint256 => bool) soulbound;
+        // contracts allowed to mint badges besides the minter
+        mapping(address => bool) badge_issuers;
+
+        // enumeration; only non zero balances are listed
+        mapping(address => TokenSet) owned_tokens; // account -> token IDs it holds
+        mapping(uint256 => HolderSet) holders; // token ID -> accounts holding it
 
         mapping(uint256 => MetaData) meta_data;
 
@@ -48,10 +120,39 @@ sol_storage! {
         PhantomData<NovaParams> phantom;
     }
 
+    // provenance of the token: how it was validated in its gallery
     pub struct MetaData{
         uint256 gallery_id;
-        uint256 system_nft_id;
-        uint256 meta_data;
+        uint256 system_nft_id; // id of the nft in the nft_libary
+        uint256 meta_data; // id of the nft in the nft_submit
+        address creator; // creator of the nft in the nft_libary
+        uint256 vote_value; // total value staked on the nft when it was validated
+        uint64 voting_start;
+        uint64 voting_end;
+        uint256 total_votes; // number of votes cast on the nft
+        uint256 rank; // final rank of the nft by stake in its gallery, starting from 1
+        uint64 minted_at; // time the first copy was claimed; zero until the provenance is recorded
+    }
+
+    // enumerable sets; `position` is the index in `list` plus one
+    pub struct TokenSet{
+        uint256[] list;
+        mapping(uint256 => uint256) position;
+    }
+
+    pub struct HolderSet{
+        address[] list;
+        mapping(address => uint256) position;
+    }
+
+    pub struct TokenApproval{
+        uint256 amount; // used up by every transfer the operator makes
+        uint64 expiry; // zero never expires
+    }
+
+    pub struct Royalty{
+        address receiver; // the creator of the nft by default
+        uint256 bps; // share of every resale paid to the receiver
     }
 
 }
@@ -59,6 +160,9 @@ sol_storage! {
 #[derive(SolidityError)]
 pub enum Erc1155Error {
     InvalidParameter(InvalidParameter),
+    ReceiverRejected(ReceiverRejected),
+    MaxSupplyExceeded(MaxSupplyExceeded),
+    Soulbound(Soulbound),
 }
 
 #[public]
@@ -69,12 +173,97 @@ impl Erc1155 {
     }
 
     // Batch balance query
-    pub fn balance_of_batch(&self, accounts: Vec<Address>, ids: Vec<U256>) -> Vec<U256> {
-        accounts
-            .iter()
-            .zip(ids.iter())
-            .map(|(&account, &id)| self.balance_of(account, id))
-            .collect()
+    pub fn balance_of_batch(
+        &self,
+        accounts: Vec<Address>,
+        ids: Vec<U256>
+    ) -> Result<Vec<U256>, Erc1155Error> {
+        if accounts.len() != ids.len() {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 101 }));
+        }
+        Ok(
+            accounts
+                .iter()
+                .zip(ids.iter())
+                .map(|(&account, &id)| self.balance_of(account, id))
+                .collect()
+        )
+    }
+
+    // ERC-165: whether this contract implements `interface_id`
+    pub fn supports_interface(&self, interface_id: FixedBytes<4>) -> bool {
+        interface_id == FixedBytes(IERC165_ID) ||
+            interface_id == FixedBytes(IERC1155_ID) ||
+            interface_id == FixedBytes(IERC1155_METADATA_URI_ID) ||
+            interface_id == FixedBytes(IERC2981_ID)
+    }
+
+    // ERC-2981: who receives the royalty of a sale of `id` at `sale_price`, and how much
+    pub fn royalty_info(&self, id: U256, sale_price: U256) -> (Address, U256) {
+        let royalty = self.royalties.getter(id);
+        let bps = royalty.bps.get();
+        // prices too large to multiply first are divided first, losing only the rounding
+        let amount = match sale_price.checked_mul(bps) {
+            Some(product) => product / U256::from(BPS),
+            None => (sale_price / U256::from(BPS)) * bps,
+        };
+        (royalty.receiver.get(), amount)
+    }
+
+    // Sets the royalty of `id`; only the minter can, and only once, when the token is first minted
+    pub fn set_royalty(
+        &mut self,
+        id: U256,
+        receiver: Address,
+        bps: U256
+    ) -> Result<(), Erc1155Error> {
+        if msg::sender() != self.minter.get() {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 1 }));
+        }
+        if bps > U256::from(BPS) {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 5 }));
+        }
+        let mut royalty = self.royalties.setter(id);
+        if royalty.receiver.get() != Address::ZERO {
+            return Ok(());
+        }
+        royalty.receiver.set(receiver);
+        royalty.bps.set(bps);
+
+        evm::log(RoyaltySet { id, receiver, bps });
+        Ok(())
+    }
+
+    // Caps the number of `id` tokens that can ever be minted; only the minter can, and only once,
+    // when the token is first created
+    pub fn set_max_supply(&mut self, id: U256, max_supply: U256) -> Result<(), Erc1155Error> {
+        if msg::sender() != self.minter.get() {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 1 }));
+        }
+        if self.max_supply.get(id) != U256::ZERO {
+            return Ok(());
+        }
+        if max_supply == U256::ZERO || max_supply < self.minted.get(id) {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 7 }));
+        }
+        self.max_supply.setter(id).set(max_supply);
+
+        evm::log(MaxSupplySet { id, max_supply });
+        Ok(())
+    }
+
+    // Edition cap of `id`; zero when the token is uncapped
+    pub fn max_supply(&self, id: U256) -> U256 {
+        self.max_supply.get(id)
+    }
+
+    // Tokens of `id` that can still be minted; U256::MAX when the token is uncapped
+    pub fn remaining(&self, id: U256) -> U256 {
+        let max_supply = self.max_supply.get(id);
+        if max_supply == U256::ZERO {
+            return U256::MAX;
+        }
+        max_supply.saturating_sub(self.minted.get(id))
     }
 
     // Grants or revokes permission for `operator` to manage all tokens of `msg.sender`.
@@ -84,7 +273,6 @@ impl Erc1155 {
             account: msg::sender(),
             operator,
             approved,
-            time: block::timestamp() as u32,
         });
     }
 
@@ -93,6 +281,71 @@ impl Erc1155 {
         self.operator_approvals.getter(account).get(operator)
     }
 
+    // Lets `operator` move up to `amount` of `msg.sender`'s `id` tokens until `expiry`
+    // (zero never expires); replaces any earlier approval of the same token
+    pub fn approve(
+        &mut self,
+        operator: Address,
+        id: U256,
+        amount: U256,
+        expiry: u64
+    ) -> Result<(), Erc1155Error> {
+        let account = msg::sender();
+        if operator == Address::ZERO || operator == account || amount == U256::ZERO {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 10 }));
+        }
+        if expiry != 0 && expiry <= block::timestamp() {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 10 }));
+        }
+
+        let mut operators = self.token_approvals.setter(account);
+        let mut tokens = operators.setter(operator);
+        let mut approval = tokens.setter(id);
+        approval.amount.set(amount);
+        approval.expiry.set(U64::from(expiry));
+
+        evm::log(Approval {
+            account,
+            operator,
+            id,
+            amount,
+            expiry,
+        });
+        Ok(())
+    }
+
+    // Removes the approval of `operator` over `msg.sender`'s `id` tokens
+    pub fn revoke_approval(&mut self, operator: Address, id: U256) {
+        let account = msg::sender();
+        let mut operators = self.token_approvals.setter(account);
+        let mut tokens = operators.setter(operator);
+        let mut approval = tokens.setter(id);
+        approval.amount.set(U256::ZERO);
+        approval.expiry.set(U64::ZERO);
+
+        evm::log(ApprovalRevoked { account, operator, id });
+    }
+
+    // Amount of `account`'s `id` tokens `operator` may still move; zero once the approval expired
+    pub fn allowance(&self, account: Address, operator: Address, id: U256) -> U256 {
+        let operators = self.token_approvals.getter(account);
+        let tokens = operators.getter(operator);
+        let approval = tokens.getter(id);
+        let expiry = approval.expiry.get().to::<u64>();
+        if expiry != 0 && block::timestamp() > expiry {
+            return U256::ZERO;
+        }
+        approval.amount.get()
+    }
+
+    // returns (amount, expiry) of the approval of `operator` over `account`'s `id` tokens
+    pub fn get_approval(&self, account: Address, operator: Address, id: U256) -> (U256, u64) {
+        let operators = self.token_approvals.getter(account);
+        let tokens = operators.getter(operator);
+        let approval = tokens.getter(id);
+        (approval.amount.get(), approval.expiry.get().to::<u64>())
+    }
+
     // Transfers tokens.
     pub fn safe_transfer_from(
         &mut self,
@@ -100,7 +353,7 @@ impl Erc1155 {
         to: Address,
         id: U256,
         amount: U256,
-        data: Vec<u8>
+        data: Bytes
     ) -> Result<(), Erc1155Error> {
         self._transfer_single(msg::sender(), from, to, id, amount)?;
         evm::log(TransferSingle {
@@ -109,10 +362,9 @@ impl Erc1155 {
             to,
             id,
             value: amount,
-            time: block::timestamp() as u32,
         });
 
-        Ok(())
+        self.check_received(msg::sender(), from, to, id, amount, data)
     }
 
     // Batch transfer of tokens.
@@ -122,7 +374,7 @@ impl Erc1155 {
         to: Address,
         ids: Vec<U256>,
         amounts: Vec<U256>,
-        data: Vec<u8>
+        data: Bytes
     ) -> Result<(), Erc1155Error> {
         if ids.len() != amounts.len() {
             return Err(
@@ -138,12 +390,11 @@ impl Erc1155 {
             operator: msg::sender(),
             from,
             to,
-            ids,
-            values: amounts,
-            time: block::timestamp() as u32,
+            ids: ids.clone(),
+            values: amounts.clone(),
         });
 
-        Ok(())
+        self.check_batch_received(msg::sender(), from, to, ids, amounts, data)
     }
 
     // Mints a token.
@@ -152,11 +403,15 @@ impl Erc1155 {
         to: Address,
         id: U256,
         amount: U256,
-        data: Vec<u8>
+        data: Bytes
     ) -> Result<(), Erc1155Error> {
         if msg::sender() != self.minter.get() {
             return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 2 }));
         }
+        if to == Address::ZERO {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 3 }));
+        }
+        self.use_supply(id, amount)?;
         let balance_to = self.balances.getter(id).getter(to).get();
 
         let mut b_e = self.balances.setter(id);
@@ -166,6 +421,7 @@ impl Erc1155 {
         let old_supply = self.total_supply.getter(id).get();
         let mut new_supply = self.total_supply.setter(id);
         new_supply.set(old_supply + amount);
+        self.track(to, id);
 
         evm::log(TransferSingle {
             operator: msg::sender(),
@@ -173,10 +429,9 @@ impl Erc1155 {
             to,
             id,
             value: amount,
-            time: block::timestamp() as u32,
         });
 
-        Ok(())
+        self.check_received(msg::sender(), Address::ZERO, to, id, amount, data)
     }
 
     pub fn _mint_batch(
@@ -184,7 +439,7 @@ impl Erc1155 {
         to: Address,
         ids: Vec<U256>,
         amounts: Vec<U256>,
-        data: Vec<u8>
+        data: Bytes
     ) -> Result<(), Erc1155Error> {
         if ids.len() != amounts.len() {
             return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 101 }));
@@ -193,8 +448,12 @@ impl Erc1155 {
         if msg::sender() != self.minter.get() {
             return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 1 }));
         }
+        if to == Address::ZERO {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 3 }));
+        }
 
         for (id, amount) in ids.iter().zip(amounts.iter()) {
+            self.use_supply(*id, *amount)?;
             let old_balance = self.balances.getter(*id).getter(to).get();
             let mut b_p = self.balances.setter(*id);
             let mut balance = b_p.setter(to);
@@ -203,6 +462,7 @@ impl Erc1155 {
             let old_supply = self.total_supply.getter(*id).get();
             let mut new_supply = self.total_supply.setter(*id);
             new_supply.set(old_supply + amount);
+            self.track(to, *id);
         }
 
         evm::log(TransferBatch {
@@ -211,58 +471,363 @@ impl Erc1155 {
             to,
             ids: ids.clone(),
             values: amounts.clone(),
-            time: block::timestamp() as u32,
         });
 
+        self.check_batch_received(msg::sender(), Address::ZERO, to, ids, amounts, data)
+    }
+
+    // Burns `amount` of `from`'s `id` tokens (the caller must be `from`, an operator of `from`,
+    // or approved for at least `amount` of the token, which the burn uses up)
+    pub fn burn(&mut self, from: Address, id: U256, amount: U256) -> Result<(), Erc1155Error> {
+        self._burn(msg::sender(), from, id, amount)?;
+        evm::log(TransferSingle {
+            operator: msg::sender(),
+            from,
+            to: Address::ZERO,
+            id,
+            value: amount,
+        });
+        Ok(())
+    }
+
+    // Burns several of `from`'s tokens at once
+    pub fn burn_batch(
+        &mut self,
+        from: Address,
+        ids: Vec<U256>,
+        amounts: Vec<U256>
+    ) -> Result<(), Erc1155Error> {
+        if ids.len() != amounts.len() {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 101 }));
+        }
+        for (id, amount) in ids.iter().zip(amounts.iter()) {
+            self._burn(msg::sender(), from, *id, *amount)?;
+        }
+        evm::log(TransferBatch {
+            operator: msg::sender(),
+            from,
+            to: Address::ZERO,
+            ids,
+            values: amounts,
+        });
+        Ok(())
+    }
+
+    // Burns `amount` of the caller's `id` tokens in exchange for the perk of `redeemer`;
+    // `data` is passed to the redeemer, e.g. the gallery a free ticket is for
+    pub fn redeem(
+        &mut self,
+        redeemer: Address,
+        id: U256,
+        amount: U256,
+        data: Bytes
+    ) -> Result<(), Erc1155Error> {
+        if !self.redeemers.get(redeemer) || amount == U256::ZERO {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 6 }));
+        }
+        if self.soulbound.get(id) {
+            return Err(Erc1155Error::Soulbound(Soulbound { id }));
+        }
+
+        let holder = msg::sender();
+        self._burn(holder, holder, id, amount)?;
+        let redemptions = self.redemptions.get(id) + amount;
+        self.redemptions.setter(id).set(redemptions);
+        evm::log(TransferSingle {
+            operator: holder,
+            from: holder,
+            to: Address::ZERO,
+            id,
+            value: amount,
+        });
+
+        let creator = self.meta_data.getter(id).creator.get();
+        let hook = IRedeemer::new(redeemer);
+        let config = Call::new_in(self);
+        match hook.on_redeem(config, holder, id, amount, creator, data.0.into()) {
+            Ok(selector) if selector == FixedBytes(ON_REDEEM) => {}
+            _ => {
+                return Err(Erc1155Error::ReceiverRejected(ReceiverRejected { receiver: redeemer }));
+            }
+        }
+
+        evm::log(Redeemed {
+            holder,
+            redeemer,
+            id,
+            amount,
+            redemptions,
+        });
+        Ok(())
+    }
+
+    // Allows or disallows redeeming tokens with `redeemer`
+    pub fn set_redeemer(&mut self, redeemer: Address, state: bool) -> Result<(), Erc1155Error> {
+        self.check_admin()?;
+        self.redeemers.setter(redeemer).set(state);
+        evm::log(SetRedeemer { redeemer, state });
         Ok(())
     }
 
+    pub fn is_redeemer(&self, redeemer: Address) -> bool {
+        self.redeemers.get(redeemer)
+    }
+
+    // Number of `id` tokens redeemed so far
+    pub fn redemptions(&self, id: U256) -> U256 {
+        self.redemptions.get(id)
+    }
+
+    // Mints the `kind` badge of `gallery_id` to `to`; only the minter or a badge issuer can
+    // a badge is soulbound and held once, so minting it again is a no-op
+    pub fn mint_badge(
+        &mut self,
+        to: Address,
+        kind: u8,
+        gallery_id: U256
+    ) -> Result<U256, Erc1155Error> {
+        let sender = msg::sender();
+        if sender != self.minter.get() && !self.badge_issuers.get(sender) {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 2 }));
+        }
+        if to == Address::ZERO {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 3 }));
+        }
+        let id = self.badge_id(kind, gallery_id)?;
+        if self.balances.getter(id).getter(to).get() != U256::ZERO {
+            return Ok(id);
+        }
+
+        self.soulbound.setter(id).set(true);
+        self.meta_data.setter(id).gallery_id.set(gallery_id);
+        self.balances.setter(id).setter(to).set(U256::from(1));
+        let old_supply = self.total_supply.getter(id).get();
+        self.total_supply.setter(id).set(old_supply + U256::from(1));
+        self.track(to, id);
+
+        evm::log(TransferSingle {
+            operator: sender,
+            from: Address::ZERO,
+            to,
+            id,
+            value: U256::from(1),
+        });
+        evm::log(BadgeMinted { to, id, kind, gallery_id });
+
+        // a badge can never be moved, so `to` is not asked to accept it: a contract
+        // without the receiver hook must not be able to revert the mint
+        Ok(id)
+    }
+
+    // Token ID of the `kind` badge of `gallery_id` (1: top-3 voter, 2: accepted creator)
+    pub fn badge_id(&self, kind: u8, gallery_id: U256) -> Result<U256, Erc1155Error> {
+        if (kind != VOTER_BADGE && kind != CREATOR_BADGE) || gallery_id >= U256::from(1) << 128 {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 8 }));
+        }
+        Ok((U256::from(1) << BADGE_BIT) | (U256::from(kind) << 128) | gallery_id)
+    }
+
+    // Whether `id` is a badge that can not be transferred
+    pub fn is_soulbound(&self, id: U256) -> bool {
+        self.soulbound.get(id)
+    }
+
+    pub fn set_badge_issuer(&mut self, issuer: Address, state: bool) -> Result<(), Erc1155Error> {
+        self.check_admin()?;
+        self.badge_issuers.setter(issuer).set(state);
+        evm::log(SetBadgeIssuer { issuer, state });
+        Ok(())
+    }
+
+    pub fn is_badge_issuer(&self, issuer: Address) -> bool {
+        self.badge_issuers.get(issuer)
+    }
+
+    // Token IDs held by `account`, from `offset`, at most `limit` of them
+    pub fn tokens_of(&self, account: Address, offset: U256, limit: U256) -> Vec<U256> {
+        let tokens = self.owned_tokens.getter(account);
+        let (start, end) = Self::page(tokens.list.len(), offset, limit);
+        (start..end).filter_map(|i| tokens.list.get(i)).collect()
+    }
+
+    // Accounts holding `id`, from `offset`, at most `limit` of them
+    pub fn holders_of(&self, id: U256, offset: U256, limit: U256) -> Vec<Address> {
+        let holders = self.holders.getter(id);
+        let (start, end) = Self::page(holders.list.len(), offset, limit);
+        (start..end).filter_map(|i| holders.list.get(i)).collect()
+    }
+
+    // Number of token IDs held by `account`
+    pub fn tokens_count(&self, account: Address) -> U256 {
+        U256::from(self.owned_tokens.getter(account).list.len())
+    }
+
+    // Number of accounts holding `id`
+    pub fn holders_count(&self, id: U256) -> U256 {
+        U256::from(self.holders.getter(id).list.len())
+    }
+
     /// Total supply
     pub fn total_supply(&self, id: U256) -> Result<U256, Erc1155Error> {
         Ok(self.total_supply.getter(id).get())
     }
 
     pub fn set_minter(&mut self, minter: Address) -> Result<(), Erc1155Error> {
-        self.check_admin().map_err(|e| { e })?;
+        self.check_admin()?;
         self.minter.set(minter);
         Ok(())
     }
 
-    // pub fn uri(&self, id: U256) -> String {
-    //     self.uris.getter(id).get_string()
-    // }
+    // Metadata uri of `id`: its own uri, else the base uri template, else an on-chain data-URI JSON
+    pub fn uri(&self, id: U256) -> String {
+        let uri = self.uris.getter(id).get_string();
+        if !uri.is_empty() {
+            return uri;
+        }
+        let base_uri = self.base_uri.get_string();
+        if !base_uri.is_empty() {
+            return base_uri;
+        }
+        self.json_uri(id)
+    }
+
+    // Sets the base uri template used by every token without its own uri
+    pub fn set_base_uri(&mut self, base_uri: String) -> Result<(), Erc1155Error> {
+        self.check_admin()?;
+        self.base_uri.set_str(base_uri);
+        Ok(())
+    }
+
+    // Sets the uri of a single token; an empty uri falls back to the base uri or on-chain JSON
+    pub fn set_token_uri(&mut self, id: U256, uri: String) -> Result<(), Erc1155Error> {
+        self.check_admin()?;
+        if self.frozen.get(id) {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 4 }));
+        }
+        self._set_uri(id, uri);
+        Ok(())
+    }
 
-    // pub fn _setURI(&mut self, id: U256, uri: String) {
-    //     self.uris.setter(id).set_str(uri.clone());
+    // Fixes the current uri of `id` forever, so collectors keep permanent metadata
+    pub fn freeze_metadata(&mut self, id: U256) -> Result<(), Erc1155Error> {
+        self.check_admin()?;
+        if self.frozen.get(id) {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 4 }));
+        }
+        let uri = self.uri(id);
+        self._set_uri(id, uri);
+        self.frozen.setter(id).set(true);
+        Ok(())
+    }
 
-    //     evm::log(URI {
-    //         value: uri,
-    //         id,
-    //     })
-    // }
+    pub fn is_frozen(&self, id: U256) -> bool {
+        self.frozen.get(id)
+    }
 
     /// Immutable token name
     pub fn name() -> String {
         NovaParams::COLLECTIONNAME.into()
     }
 
+    // frozen metadata is left as it is, so later claims of the same token still succeed;
+    // the vote value is part of the provenance and is only written until that is recorded
+    #[allow(non_snake_case)]
     pub fn setData(
         &mut self,
         id: U256,
         g_id: U256,
         s_n_id: U256,
-        m_d_id: U256
+        m_d_id: U256,
+        creator: Address,
+        vote_value: U256
     ) -> Result<(), Erc1155Error> {
         if msg::sender() != self.minter.get() {
             return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 1 }));
         }
+        if self.frozen.get(id) {
+            return Ok(());
+        }
         let mut state = self.meta_data.setter(id);
         state.gallery_id.set(g_id);
         state.system_nft_id.set(s_n_id);
         state.meta_data.set(m_d_id);
+        state.creator.set(creator);
+        if state.minted_at.get() == U64::ZERO {
+            state.vote_value.set(vote_value);
+        }
+
+        evm::log(URI {
+            value: self.uri(id),
+            id,
+        });
+        Ok(())
+    }
+
+    // Records the voting window and result of `id`; only the minter can, once, at the first claim
+    pub fn set_provenance(
+        &mut self,
+        id: U256,
+        voting_start: u64,
+        voting_end: u64,
+        total_votes: U256,
+        rank: U256
+    ) -> Result<(), Erc1155Error> {
+        if msg::sender() != self.minter.get() {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 1 }));
+        }
+        let mut state = self.meta_data.setter(id);
+        if state.minted_at.get() != U64::ZERO {
+            return Ok(());
+        }
+        state.voting_start.set(U64::from(voting_start));
+        state.voting_end.set(U64::from(voting_end));
+        state.total_votes.set(total_votes);
+        state.rank.set(rank);
+        state.minted_at.set(U64::from(block::timestamp()));
+
+        if !self.frozen.get(id) {
+            evm::log(URI {
+                value: self.uri(id),
+                id,
+            });
+        }
         Ok(())
     }
 
+    // whether the provenance of `id` has been recorded, i.e. its first copy was claimed
+    pub fn has_provenance(&self, id: U256) -> bool {
+        self.meta_data.getter(id).minted_at.get() != U64::ZERO
+    }
+
+    // returns (gallery id, nft_libary id, nft_submit id, creator, voting start, voting end,
+    // total stake, total votes, rank, mint time)
+    #[allow(clippy::type_complexity)]
+    pub fn get_provenance(
+        &self,
+        id: U256
+    ) -> Result<
+        (U256, U256, U256, Address, u64, u64, U256, U256, U256, u64),
+        Erc1155Error
+    > {
+        let state = self.meta_data.getter(id);
+        if state.minted_at.get() == U64::ZERO {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 9 }));
+        }
+        Ok((
+            state.gallery_id.get(),
+            state.system_nft_id.get(),
+            state.meta_data.get(),
+            state.creator.get(),
+            state.voting_start.get().to::<u64>(),
+            state.voting_end.get().to::<u64>(),
+            state.vote_value.get(),
+            state.total_votes.get(),
+            state.rank.get(),
+            state.minted_at.get().to::<u64>(),
+        ))
+    }
+
+    #[allow(non_snake_case)]
     pub fn getData(&self, id: U256) -> Result<[U256; 3], Erc1155Error> {
         let state = self.meta_data.getter(id);
         Ok([state.gallery_id.get(), state.system_nft_id.get(), state.meta_data.get()])
@@ -270,6 +835,31 @@ impl Erc1155 {
 }
 
 impl Erc1155 {
+    // Sets the uri of `id` and emits the standard URI event
+    pub fn _set_uri(&mut self, id: U256, uri: String) {
+        self.uris.setter(id).set_str(uri);
+        evm::log(URI {
+            value: self.uri(id),
+            id,
+        });
+    }
+
+    // On-chain metadata of `id` as a data-URI JSON
+    pub fn json_uri(&self, id: U256) -> String {
+        let state = self.meta_data.getter(id);
+        format!(
+            "data:application/json;utf8,{{\"name\":\"{} {}\",\"attributes\":[{{\"trait_type\":\"gallery_id\",\"value\":\"{}\"}},{{\"trait_type\":\"nft_submit_id\",\"value\":\"{}\"}},{{\"trait_type\":\"creator\",\"value\":\"{}\"}},{{\"trait_type\":\"vote_value\",\"value\":\"{}\"}},{{\"trait_type\":\"total_votes\",\"value\":\"{}\"}},{{\"trait_type\":\"rank\",\"value\":\"{}\"}}]}}",
+            NovaParams::COLLECTIONNAME,
+            id,
+            state.gallery_id.get(),
+            state.meta_data.get(),
+            state.creator.get(),
+            state.vote_value.get(),
+            state.total_votes.get(),
+            state.rank.get()
+        )
+    }
+
     // Internal single token transfer.
     pub fn _transfer_single(
         &mut self,
@@ -280,10 +870,17 @@ impl Erc1155 {
         amount: U256
     ) -> Result<(), Erc1155Error> {
         let balance_from = self.balances.getter(id).getter(from).get();
-        let balance_to = self.balances.getter(id).getter(to).get();
 
         if operator != from && !self.is_approved_for_all(from, operator) {
-            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 17 }));
+            self.spend_approval(from, operator, id, amount)?;
+        }
+
+        if self.soulbound.get(id) {
+            return Err(Erc1155Error::Soulbound(Soulbound { id }));
+        }
+
+        if to == Address::ZERO {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 3 }));
         }
 
         if balance_from < amount {
@@ -297,16 +894,175 @@ impl Erc1155 {
             balance_from_x.set(balance_from - amount);
         }
 
-        // Update `to` balance
+        // Update `to` balance; read after the `from` write so a transfer to oneself nets to zero
         {
+            let balance_to = self.balances.getter(id).getter(to).get();
             let mut b_y = self.balances.setter(id);
             let mut balance_to_y = b_y.setter(to);
             balance_to_y.set(balance_to + amount);
         }
 
+        self.track(from, id);
+        self.track(to, id);
+        Ok(())
+    }
+
+    // Calls `onERC1155Received` when `to` is a contract; it must return the hook's selector
+    pub fn check_received(
+        &mut self,
+        operator: Address,
+        from: Address,
+        to: Address,
+        id: U256,
+        amount: U256,
+        data: Bytes
+    ) -> Result<(), Erc1155Error> {
+        if !to.has_code() {
+            return Ok(());
+        }
+        let receiver = IErc1155Receiver::new(to);
+        let config = Call::new_in(self);
+        match receiver.on_erc_1155_received(config, operator, from, id, amount, data.0.into()) {
+            Ok(selector) if selector == FixedBytes(ON_ERC1155_RECEIVED) => Ok(()),
+            _ => Err(Erc1155Error::ReceiverRejected(ReceiverRejected { receiver: to })),
+        }
+    }
+
+    // Calls `onERC1155BatchReceived` when `to` is a contract; it must return the hook's selector
+    pub fn check_batch_received(
+        &mut self,
+        operator: Address,
+        from: Address,
+        to: Address,
+        ids: Vec<U256>,
+        amounts: Vec<U256>,
+        data: Bytes
+    ) -> Result<(), Erc1155Error> {
+        if !to.has_code() {
+            return Ok(());
+        }
+        let receiver = IErc1155Receiver::new(to);
+        let config = Call::new_in(self);
+        match receiver.on_erc_1155_batch_received(config, operator, from, ids, amounts, data.0.into()) {
+            Ok(selector) if selector == FixedBytes(ON_ERC1155_BATCH_RECEIVED) => Ok(()),
+            _ => Err(Erc1155Error::ReceiverRejected(ReceiverRejected { receiver: to })),
+        }
+    }
+
+    // Internal burn; lowers the balance of `from` and the total supply of `id`,
+    // using up the approval of `operator` over the token like a transfer does
+    pub fn _burn(
+        &mut self,
+        operator: Address,
+        from: Address,
+        id: U256,
+        amount: U256
+    ) -> Result<(), Erc1155Error> {
+        if operator != from && !self.is_approved_for_all(from, operator) {
+            self.spend_approval(from, operator, id, amount)?;
+        }
+
+        let balance_from = self.balances.getter(id).getter(from).get();
+        if balance_from < amount {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 111 }));
+        }
+        self.balances.setter(id).setter(from).set(balance_from - amount);
+
+        let old_supply = self.total_supply.getter(id).get();
+        self.total_supply.setter(id).set(old_supply - amount);
+        self.track(from, id);
+        Ok(())
+    }
+
+    // Uses up `amount` of the approval of `operator` over `from`'s `id` tokens
+    pub fn spend_approval(
+        &mut self,
+        from: Address,
+        operator: Address,
+        id: U256,
+        amount: U256
+    ) -> Result<(), Erc1155Error> {
+        let allowance = self.allowance(from, operator, id);
+        if allowance < amount {
+            return Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 17 }));
+        }
+        let mut operators = self.token_approvals.setter(from);
+        let mut tokens = operators.setter(operator);
+        tokens.setter(id).amount.set(allowance - amount);
+        Ok(())
+    }
+
+    // Counts `amount` new `id` tokens against the edition cap of the token
+    pub fn use_supply(&mut self, id: U256, amount: U256) -> Result<(), Erc1155Error> {
+        let max_supply = self.max_supply.get(id);
+        let minted = self.minted.get(id) + amount;
+        if max_supply != U256::ZERO && minted > max_supply {
+            return Err(
+                Erc1155Error::MaxSupplyExceeded(MaxSupplyExceeded {
+                    id,
+                    max_supply,
+                    minted,
+                })
+            );
+        }
+        self.minted.setter(id).set(minted);
         Ok(())
     }
 
+    // Lists `account` as a holder of `id` while its balance is non zero, and drops it once it is zero
+    pub fn track(&mut self, account: Address, id: U256) {
+        let held = self.balances.getter(id).getter(account).get() != U256::ZERO;
+
+        let mut tokens = self.owned_tokens.setter(account);
+        let position = tokens.position.get(id);
+        if held && position == U256::ZERO {
+            tokens.list.push(id);
+            let len = U256::from(tokens.list.len());
+            tokens.position.setter(id).set(len);
+        } else if !held && position != U256::ZERO {
+            // swap the last token into the removed slot
+            let index = position.to::<usize>() - 1;
+            let last_index = tokens.list.len() - 1;
+            if index != last_index {
+                let last = tokens.list.get(last_index).unwrap_or_default();
+                if let Some(mut slot) = tokens.list.setter(index) {
+                    slot.set(last);
+                }
+                tokens.position.setter(last).set(position);
+            }
+            tokens.list.pop();
+            tokens.position.setter(id).set(U256::ZERO);
+        }
+
+        let mut holders = self.holders.setter(id);
+        let position = holders.position.get(account);
+        if held && position == U256::ZERO {
+            holders.list.push(account);
+            let len = U256::from(holders.list.len());
+            holders.position.setter(account).set(len);
+        } else if !held && position != U256::ZERO {
+            // swap the last holder into the removed slot
+            let index = position.to::<usize>() - 1;
+            let last_index = holders.list.len() - 1;
+            if index != last_index {
+                let last = holders.list.get(last_index).unwrap_or_default();
+                if let Some(mut slot) = holders.list.setter(index) {
+                    slot.set(last);
+                }
+                holders.position.setter(last).set(position);
+            }
+            holders.list.pop();
+            holders.position.setter(account).set(U256::ZERO);
+        }
+    }
+
+    // Bounds of a page of `limit` entries starting at `offset` in a list of `len` entries
+    pub fn page(len: usize, offset: U256, limit: U256) -> (usize, usize) {
+        let start = offset.saturating_to::<usize>().min(len);
+        let end = start.saturating_add(limit.saturating_to::<usize>()).min(len);
+        (start, end)
+    }
+
     pub fn check_admin(&mut self) -> Result<(), Erc1155Error> {
         let default_x = Address::from([0x00; 20]);
         if self.admin.get() != default_x && msg::sender() != self.admin.get() {
@@ -322,3 +1078,6 @@ impl Erc1155 {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests;
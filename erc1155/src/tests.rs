@@ -0,0 +1,211 @@
+//! Unit tests run the contract against an in-memory stand-in of the Stylus host:
+//! storage lives in a per-thread map, the caller, block and contract address
+//! are the fixed values below, and there are no other contracts to call.
+
+use super::*;
+
+// the account every call is made from; it becomes the admin on its first admin call
+const SENDER: Address = Address::new([0x11; 20]);
+const COLLECTION: Address = Address::new([0x22; 20]);
+const NOW: u64 = 1_700_000_000;
+const BLOCK: u64 = 1_000;
+
+mod host {
+    use super::{ Address, BLOCK, NOW, SENDER, COLLECTION };
+    use sha3::{ Digest, Keccak256 };
+    use std::{ cell::RefCell, collections::HashMap };
+
+    thread_local! {
+        static STORAGE: RefCell<HashMap<[u8; 32], [u8; 32]>> = RefCell::new(HashMap::new());
+    }
+
+    unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+    }
+
+    unsafe fn address(dest: *mut u8, address: Address) {
+        write(dest, address.as_slice());
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = STORAGE.with(|s| s.borrow().get(&key).copied().unwrap_or_default());
+        write(dest, &value);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = *(value as *const [u8; 32]);
+        STORAGE.with(|s| s.borrow_mut().insert(key, value));
+    }
+
+    #[no_mangle]
+    extern "C" fn storage_flush_cache(_clear: bool) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+        let input = std::slice::from_raw_parts(bytes, len);
+        write(output, &Keccak256::digest(input));
+    }
+
+    #[no_mangle]
+    extern "C" fn emit_log(_data: *const u8, _len: usize, _topics: usize) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        address(sender, SENDER);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_value(value: *mut u8) {
+        write(value, &[0; 32]);
+    }
+
+    #[no_mangle]
+    extern "C" fn msg_reentrant() -> bool {
+        false
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn contract_address(dest: *mut u8) {
+        address(dest, COLLECTION);
+    }
+
+    #[no_mangle]
+    extern "C" fn block_timestamp() -> u64 {
+        NOW
+    }
+
+    #[no_mangle]
+    extern "C" fn block_number() -> u64 {
+        BLOCK
+    }
+
+    #[no_mangle]
+    extern "C" fn chainid() -> u64 {
+        42161
+    }
+
+    #[no_mangle]
+    extern "C" fn account_code_size(_address: *const u8) -> usize {
+        0
+    }
+
+    // there are no other contracts: every call reverts without return data
+    #[no_mangle]
+    unsafe extern "C" fn call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _value: *const u8,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn delegate_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn static_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    extern "C" fn read_return_data(_dest: *mut u8, _offset: usize, _size: usize) -> usize {
+        0
+    }
+
+    #[no_mangle]
+    extern "C" fn return_data_size() -> usize {
+        0
+    }
+}
+
+// a fresh collection; storage is empty on every test thread
+fn collection() -> Erc1155 {
+    unsafe { <Erc1155 as stylus_sdk::storage::StorageType>::new(U256::ZERO, 0) }
+}
+
+fn with_royalty(id: U256, receiver: Address, bps: u64) -> Erc1155 {
+    let mut collection = collection();
+    let mut royalty = collection.royalties.setter(id);
+    royalty.receiver.set(receiver);
+    royalty.bps.set(U256::from(bps));
+    collection
+}
+
+#[test]
+fn royalty_is_a_share_of_the_sale_price() {
+    let (id, creator) = (U256::from(7), Address::new([0xc1; 20]));
+    let collection = with_royalty(id, creator, 250);
+    assert_eq!(collection.royalty_info(id, U256::from(10_000)), (creator, U256::from(250)));
+    // 999 * 250 / 10_000 rounds down
+    assert_eq!(collection.royalty_info(id, U256::from(999)), (creator, U256::from(24)));
+    assert_eq!(collection.royalty_info(U256::from(8), U256::from(10_000)), (Address::ZERO, U256::ZERO));
+}
+
+#[test]
+fn royalty_of_a_huge_price_does_not_wrap() {
+    let (id, creator) = (U256::from(7), Address::new([0xc1; 20]));
+    let collection = with_royalty(id, creator, 250);
+    let (_, royalty) = collection.royalty_info(id, U256::MAX);
+    assert_eq!(royalty, (U256::MAX / U256::from(10_000)) * U256::from(250));
+    assert!(royalty < U256::MAX);
+
+    let collection = with_royalty(id, creator, 10_000);
+    assert_eq!(collection.royalty_info(id, U256::MAX).1, U256::MAX / U256::from(10_000) * U256::from(10_000));
+}
+
+// a collection where `holder` has 100 of token 7
+fn holding(holder: Address) -> Erc1155 {
+    let mut collection = collection();
+    let id = U256::from(7);
+    collection.balances.setter(id).setter(holder).set(U256::from(100));
+    collection.total_supply.setter(id).set(U256::from(100));
+    collection
+}
+
+#[test]
+fn burning_uses_up_a_token_approval() {
+    let (holder, id) = (Address::new([0xa1; 20]), U256::from(7));
+    let mut collection = holding(holder);
+    collection.token_approvals.setter(holder).setter(SENDER).setter(id).amount.set(U256::from(50));
+
+    assert!(collection.burn(holder, id, U256::from(30)).is_ok());
+    assert_eq!(collection.allowance(holder, SENDER, id), U256::from(20));
+    assert_eq!(collection.balance_of(holder, id), U256::from(70));
+    assert_eq!(collection.total_supply.getter(id).get(), U256::from(70));
+
+    let burned = collection.burn(holder, id, U256::from(30));
+    assert!(matches!(burned, Err(Erc1155Error::InvalidParameter(InvalidParameter { point: 17 }))));
+    assert_eq!(collection.balance_of(holder, id), U256::from(70));
+}
+
+#[test]
+fn a_transfer_to_oneself_keeps_the_balance() {
+    let id = U256::from(7);
+    let mut collection = holding(SENDER);
+    assert!(collection._transfer_single(SENDER, SENDER, SENDER, id, U256::from(40)).is_ok());
+    assert_eq!(collection.balance_of(SENDER, id), U256::from(100));
+}
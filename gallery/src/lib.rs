@@ -1,5 +1,5 @@
 // Allow `cargo stylus export-abi` to generate a main function.
-#![cfg_attr(not(feature = "export-abi"), no_main)]
+#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
 extern crate alloc;
 
 /// Import items from the SDK. The prelude contains common traits and macros.
@@ -41,6 +41,8 @@ sol_storage! {
         // uint32[] leaderboard;
 
         VotingCondition conditions;
+        // set by the owner before voting or by the admin until voting ends; ticket_sales refunds the tickets
+        bool cancelled;
     }
 
     pub struct VotingCondition {
@@ -66,6 +68,12 @@ sol! {
     // event to show that a user has joined a gallery
     event JoinedGallery(uint256 indexed gallery_index, address indexed member, uint64 time );
 
+    // event to show that a gallery has been cancelled
+    event GalleryCancelled(uint256 indexed gallery_index, address indexed cancelled_by, uint64 time);
+
+    // event to show the owner edited a gallery before voting; holds the old and the new values
+    event GalleryUpdated(uint256 indexed gallery_index, string old_name, string name, string old_meta_data, string meta_data, uint256 old_price, uint256 price, uint64 old_voting_start, uint64 voting_start, uint64 old_voting_end, uint64 voting_end, uint256 old_minimum_staking_amount, uint256 minimum_staking_amount, uint64 time);
+
 
     // my error
     // error to show invalid parameter
@@ -97,13 +105,7 @@ impl Subject {
         voting_end: u64,
         minimum_staking_amount: U256
     ) -> Result<(), GalleryError> {
-        if
-            name.is_empty() ||
-            meta_data.is_empty() ||
-            voting_start < block::timestamp() ||
-            voting_end < block::timestamp() ||
-            voting_start >= voting_end
-        {
+        if !self.valid_params(&name, &meta_data, voting_start, voting_end) {
             return Err(
                 GalleryError::InvalidParameter(InvalidParameter {
                     point: 0,
@@ -143,6 +145,141 @@ impl Subject {
         Ok(())
     }
 
+    // lets the owner change the gallery until voting starts; the values are validated as on creation
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_gallery(
+        &mut self,
+        gallery_index: U256,
+        name: String,
+        meta_data: String,
+        price: U256,
+        voting_start: u64,
+        voting_end: u64,
+        minimum_staking_amount: U256
+    ) -> Result<(), GalleryError> {
+        if gallery_index == U256::from(0) || gallery_index > self.available_index.get() {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 1,
+                })
+            );
+        }
+
+        let gallery = self.gallery.getter(gallery_index);
+        if gallery.owner.get() != msg::sender() {
+            return Err(
+                GalleryError::DeniedAccess(DeniedAccess {
+                    gallery_index,
+                })
+            );
+        }
+        if gallery.cancelled.get() {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 5,
+                })
+            );
+        }
+
+        let old_voting_start = gallery.conditions.voting_start.get().to::<u64>();
+        if block::timestamp() >= old_voting_start {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 4,
+                })
+            );
+        }
+
+        if !self.valid_params(&name, &meta_data, voting_start, voting_end) {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 0,
+                })
+            );
+        }
+
+        let old_name = gallery.name.get_string();
+        let old_meta_data = gallery.meta_data.get_string();
+        let old_price = gallery.price.get();
+        let old_voting_end = gallery.conditions.voting_end.get().to::<u64>();
+        let old_minimum_staking_amount = gallery.conditions.minimum_staking_amount.get();
+
+        let mut gallery = self.gallery.setter(gallery_index);
+        gallery.name.set_str(name.clone());
+        gallery.meta_data.set_str(meta_data.clone());
+        gallery.price.set(price);
+        gallery.conditions.voting_start.set(U64::from(voting_start));
+        gallery.conditions.voting_end.set(U64::from(voting_end));
+        gallery.conditions.minimum_staking_amount.set(minimum_staking_amount);
+
+        evm::log(GalleryUpdated {
+            gallery_index,
+            old_name,
+            name,
+            old_meta_data,
+            meta_data,
+            old_price,
+            price,
+            old_voting_start,
+            voting_start,
+            old_voting_end,
+            voting_end,
+            old_minimum_staking_amount,
+            minimum_staking_amount,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    // cancels the gallery; the owner can until voting starts, the admin until voting ends
+    // once voting has ended the ticket payments can be released to the owner, so there is nothing left to refund
+    pub fn cancel_gallery(&mut self, gallery_index: U256) -> Result<(), GalleryError> {
+        if gallery_index == U256::from(0) || gallery_index > self.available_index.get() {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 1,
+                })
+            );
+        }
+
+        let gallery = self.gallery.getter(gallery_index);
+        if gallery.cancelled.get() {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 5,
+                })
+            );
+        }
+
+        if block::timestamp() > gallery.conditions.voting_end.get().to::<u64>() {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 6,
+                })
+            );
+        }
+
+        let sender = msg::sender();
+        let voting_start = gallery.conditions.voting_start.get().to::<u64>();
+        let by_owner = gallery.owner.get() == sender && block::timestamp() < voting_start;
+        if !by_owner && sender != self.admin.get() {
+            return Err(
+                GalleryError::DeniedAccess(DeniedAccess {
+                    gallery_index,
+                })
+            );
+        }
+
+        self.gallery.setter(gallery_index).cancelled.set(true);
+
+        evm::log(GalleryCancelled {
+            gallery_index,
+            cancelled_by: sender,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
     pub fn buy_ticket(&mut self, gallery_index: U256, user: Address) -> Result<(), GalleryError> {
         if msg::sender() != self.allowed_contract.get() {
             return Err(
@@ -151,6 +288,13 @@ impl Subject {
                 })
             );
         }
+        if self.gallery.getter(gallery_index).cancelled.get() {
+            return Err(
+                GalleryError::InvalidParameter(InvalidParameter {
+                    point: 5,
+                })
+            );
+        }
 
         let mut gallery = self.gallery.setter(gallery_index);
         let attendes = gallery.attendes.get();
@@ -200,6 +344,7 @@ impl Subject {
         self.available_index.get()
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn get_gallery(
         &self,
         gallery_index: U256
@@ -214,7 +359,7 @@ impl Subject {
 
         let gallery = self.gallery.getter(gallery_index);
 
-        return Ok((
+        Ok((
             gallery.owner.get(),
             gallery.name.get_string(),
             gallery.meta_data.get_string(),
@@ -224,7 +369,7 @@ impl Subject {
             gallery.conditions.voting_end.get().to::<u64>(),
             gallery.conditions.voting_start.get().to::<u64>(),
             gallery.conditions.minimum_staking_amount.get(),
-        ));
+        ))
     }
 
     pub fn get_user_status(&self, gallery_index: U256, user: Address) -> bool {
@@ -233,6 +378,10 @@ impl Subject {
         status.get()
     }
 
+    pub fn is_cancelled(&self, gallery_index: U256) -> bool {
+        self.gallery.getter(gallery_index).cancelled.get()
+    }
+
     pub fn in_session(&self, gallery_index: U256) -> bool {
         let condition = self.gallery.getter(gallery_index);
         let start = condition.conditions.voting_start.get();
@@ -260,10 +409,9 @@ impl Subject {
             _ => None, // Return None if the state is not recognized
         };
 
-        if let Some(data) = data {
-            return Ok(data);
-        } else {
-            return Err(GalleryError::NoData(NoData {}));
+        match data {
+            Some(data) => Ok(data),
+            None => Err(GalleryError::NoData(NoData {})),
         }
     }
 
@@ -285,3 +433,23 @@ impl Subject {
         }
     }
 }
+
+// helper functions
+
+impl Subject {
+    // checks the gallery values given on creation or update;
+    // the name and meta_data can not be empty and the voting window must be ahead of now
+    pub fn valid_params(
+        &self,
+        name: &str,
+        meta_data: &str,
+        voting_start: u64,
+        voting_end: u64
+    ) -> bool {
+        !name.is_empty() &&
+            !meta_data.is_empty() &&
+            voting_start >= block::timestamp() &&
+            voting_end >= block::timestamp() &&
+            voting_start < voting_end
+    }
+}
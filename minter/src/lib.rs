@@ -1,4 +1,6 @@
 #![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+// the callers sol_interface! generates for setData take more than seven arguments
+#![allow(clippy::too_many_arguments)]
 extern crate alloc;
 
 use alloy_primitives::{ Address, U256 };
@@ -7,6 +9,13 @@ use alloy_sol_types::sol;
 
 use stylus_sdk::call::Call;
 
+// copies minted for the top three voters of an nft (3 + 2 + 1); caps the supply of every SFT
+const EDITION_SIZE: u64 = 6;
+
+// soulbound badge kinds on the erc1155 contract
+const VOTER_BADGE: u8 = 1; // top-3 voter in the gallery
+const CREATOR_BADGE: u8 = 2; // accepted creator in the gallery
+
 // this contract is the safe contract allowed to take the user votes
 sol_storage! {
     #[entrypoint]
@@ -18,6 +27,8 @@ sol_storage! {
         address nft_storage; // stores the nft_submit contract address
         address gallery_c; // stores the gallery contract address
         address erc1155; // contract address of the token contract
+        uint256 royalty_bps; // royalty paid to the creator on every resale, in basis points
+        mapping(address => mapping(uint256 => bool)) has_creator_badge; // creator badge already given for the gallery
     }
 }
 
@@ -30,7 +41,7 @@ sol_interface! {
     // interface of the gallery contract
     interface ISubject {
         function getGallery(uint256 gallery_index) external view returns (address, string memory, string memory, uint32, uint64, uint256, uint64, uint64, uint256);
-  
+        function isCancelled(uint256 gallery_index) external view returns (bool);
     }    
 
    
@@ -38,23 +49,32 @@ sol_interface! {
     //  interface of the nft_libary contract
     interface IMainx {
         function getNft(uint256 gallery_index, uint256 nft_id, bool raw) external view returns (address, uint8, uint256);
+        function nftListLen(uint256 gallery_id) external view returns (uint256, uint256);
     }
 
     //interface of the nft_submit contract
 
     interface INftStorage {
         function systemMint(uint256 nft_id) external;
+        function getNftData(uint256 nft_id) external view returns (address, string memory, uint256);
     }
 
     //interface of the erc1155 contract 
     interface IErc1155 {
-        function mint(address to, uint256 id, uint256 amount, uint8[] memory data) external;
-        function setData(uint256 id, uint256 g_id, uint256 s_n_id, uint256 m_d_id) external;
+        function mint(address to, uint256 id, uint256 amount, bytes calldata data) external;
+        function setData(uint256 id, uint256 g_id, uint256 s_n_id, uint256 m_d_id, address creator, uint256 vote_value) external;
+        function setRoyalty(uint256 id, address receiver, uint256 bps) external;
+        function setMaxSupply(uint256 id, uint256 max_supply) external;
+        function setProvenance(uint256 id, uint64 voting_start, uint64 voting_end, uint256 total_votes, uint256 rank) external;
+        function hasProvenance(uint256 id) external view returns (bool);
+        function mintBadge(address to, uint8 kind, uint256 gallery_id) external returns (uint256);
     }
 
      // interface of the unsafe stake contract
     interface IStake {
         function getPosition(uint256 gallery_id, uint256 nft_id, address user) external view returns (uint8);
+        function getTotalStake(uint256 gallery_id, uint256 nft_id) external view returns (uint256);
+        function getTotalVotes(uint256 gallery_id, uint256 nft_id) external view returns (uint256);
     }
 }
 
@@ -72,10 +92,11 @@ pub enum MinterError {
 #[public]
 impl Minter {
     // allows users to cast a safe vote
+    #[allow(non_snake_case)]
     pub fn claim_SFT(&mut self, gallery_id: U256, nft_id: U256) -> Result<(), MinterError> {
         // get the gallery info
-        let end = match self.get_gal_info(gallery_id) {
-            Ok(end) => end,
+        let (start, end) = match self.get_gal_info(gallery_id) {
+            Ok(window) => window,
             Err(_) => {
                 return Err(
                     MinterError::InvalidParameter(InvalidParameter {
@@ -86,6 +107,7 @@ impl Minter {
         };
 
         self.check_time(end)?; //makes sure voting period has ended
+        self.check_cancelled(gallery_id)?; // nothing is minted for a cancelled gallery
 
         if self.has_minted.getter(msg::sender()).getter(gallery_id).get() {
             return Err(
@@ -110,10 +132,31 @@ impl Minter {
             }
         };
         let nft_storage_id = self.get_nft(gallery_id, nft_id)?;
+        let creator = self.get_creator(nft_storage_id)?;
         self.set_libary(nft_storage_id)?;
 
+        self.set_max_supply(nft_storage_id)?;
         self.mint(nft_storage_id, U256::from(amount))?;
-        self.set_data(nft_storage_id, gallery_id, nft_id)?;
+
+        // the metadata and the result of the vote are recorded once, by the first claim of the nft;
+        // the rank walks every accepted nft of the gallery, so it is not recomputed afterwards
+        if !self.has_provenance(nft_storage_id)? {
+            let vote_value = self.get_stake(gallery_id, nft_id)?;
+            self.set_data(nft_storage_id, gallery_id, nft_id, creator, vote_value)?;
+            let votes = self.get_votes(gallery_id, nft_id)?;
+            let rank = self.get_rank(gallery_id, vote_value)?;
+            self.set_provenance(nft_storage_id, start, end, votes, rank)?;
+        }
+        self.set_royalty(nft_storage_id, creator)?;
+
+        // the results are final; record the curation of the voter and the creation of the creator.
+        // badges are best-effort, a failed badge never blocks the claim of the SFT
+        self.mint_badge(msg::sender(), VOTER_BADGE, gallery_id);
+        if !self.has_creator_badge.getter(creator).get(gallery_id)
+            && self.mint_badge(creator, CREATOR_BADGE, gallery_id)
+        {
+            self.has_creator_badge.setter(creator).setter(gallery_id).set(true);
+        }
 
         let mut minting_state = self.has_minted.setter(msg::sender());
         let mut m_s_h = minting_state.setter(gallery_id);
@@ -130,7 +173,7 @@ impl Minter {
         erc1155: Address,
         nft_storage: Address
     ) -> Result<(), MinterError> {
-        self.check_admin().map_err(|e| { e })?; // makes sure only the admin can call this function
+        self.check_admin()?; // makes sure only the admin can call this function
         self.stake.set(stake);
         self.gallery_c.set(gallery);
         self.nft_libary.set(libary);
@@ -139,20 +182,39 @@ impl Minter {
         Ok(())
     }
 
+    // sets the royalty, in basis points, given to creators of newly minted nfts
+    pub fn set_royalty_bps(&mut self, royalty_bps: U256) -> Result<(), MinterError> {
+        self.check_admin()?;
+        if royalty_bps > U256::from(10_000) {
+            return Err(
+                MinterError::InvalidParameter(InvalidParameter {
+                    point: 203,
+                })
+            );
+        }
+        self.royalty_bps.set(royalty_bps);
+        Ok(())
+    }
+
+    pub fn get_royalty_bps(&self) -> U256 {
+        self.royalty_bps.get()
+    }
+
     pub fn has_claimed(&self, gallery_id: U256) -> bool {
         self.has_minted.getter(msg::sender()).getter(gallery_id).get()
     }
 }
 
 impl Minter {
-    // info returns end
+    // info returns (voting start, voting end)
     // get the gallery infromation
-    pub fn get_gal_info(&self, gallery_index: U256) -> Result<u64, ()> {
+    #[allow(clippy::result_unit_err)]
+    pub fn get_gal_info(&self, gallery_index: U256) -> Result<(u64, u64), ()> {
         let address = self.gallery_c.get();
         let gallery_contract = ISubject::new(address);
         let config = Call::new();
         let data = gallery_contract.get_gallery(config, gallery_index).expect("drat");
-        Ok(data.6)
+        Ok((data.7, data.6))
     }
 
     // function to make sure voting period has ended
@@ -168,6 +230,21 @@ impl Minter {
         )
     }
 
+    // function to make sure the gallery has not been cancelled
+    pub fn check_cancelled(&self, gallery_id: U256) -> Result<(), MinterError> {
+        let address = self.gallery_c.get();
+        let gallery_contract = ISubject::new(address);
+        let config = Call::new();
+        if gallery_contract.is_cancelled(config, gallery_id).expect("drat") {
+            return Err(
+                MinterError::InvalidParameter(InvalidParameter {
+                    point: 204,
+                })
+            );
+        }
+        Ok(())
+    }
+
     // control the admin state
     pub fn check_admin(&mut self) -> Result<bool, MinterError> {
         let default_x: Address = Address::from([0x00; 20]);
@@ -180,10 +257,8 @@ impl Minter {
             );
         } else if self.admin.get() == default_x {
             self.admin.set(msg::sender());
-            return Ok(true);
-        } else {
-            return Ok(true);
         }
+        Ok(true)
     }
 
     // get the nft index of the nft_submit
@@ -214,6 +289,66 @@ impl Minter {
             Err(_) => Err(MinterError::InvalidParameter(InvalidParameter { point: 81 })),
         }
     }
+    // get the original creator of the nft recorded in the nft_submit
+    pub fn get_creator(&self, nft_storage_id: U256) -> Result<Address, MinterError> {
+        let address = self.nft_storage.get();
+        let storage_contract = INftStorage::new(address);
+        let config = Call::new();
+        match storage_contract.get_nft_data(config, nft_storage_id) {
+            Ok((creator, _, _)) => Ok(creator),
+            Err(_) => Err(MinterError::InvalidParameter(InvalidParameter { point: 182 })),
+        }
+    }
+
+    // get the total value staked on the nft
+    pub fn get_stake(&self, gallery_id: U256, nft_id: U256) -> Result<U256, MinterError> {
+        let address = self.stake.get();
+        let stake_contract = IStake::new(address);
+        let config = Call::new();
+        match stake_contract.get_total_stake(config, gallery_id, nft_id) {
+            Ok(stake) => Ok(stake),
+            Err(_) => Err(MinterError::InvalidParameter(InvalidParameter { point: 81 })),
+        }
+    }
+
+    // get the number of votes cast on the nft
+    pub fn get_votes(&self, gallery_id: U256, nft_id: U256) -> Result<U256, MinterError> {
+        let address = self.stake.get();
+        let stake_contract = IStake::new(address);
+        let config = Call::new();
+        match stake_contract.get_total_votes(config, gallery_id, nft_id) {
+            Ok(votes) => Ok(votes),
+            Err(_) => Err(MinterError::InvalidParameter(InvalidParameter { point: 81 })),
+        }
+    }
+
+    // whether the first claim of the SFT already recorded its provenance
+    pub fn has_provenance(&self, token_id: U256) -> Result<bool, MinterError> {
+        let meta_date_contract = IErc1155::new(*self.erc1155);
+        meta_date_contract
+            .has_provenance(Call::new(), token_id)
+            .map_err(|_| MinterError::InvalidParameter(InvalidParameter { point: 11 }))
+    }
+
+    // final rank of an nft with `stake` among the accepted nfts of the gallery, starting from 1
+    pub fn get_rank(&self, gallery_id: U256, stake: U256) -> Result<U256, MinterError> {
+        let libary_contract = IMainx::new(self.nft_libary.get());
+        let (_, accepted) = libary_contract
+            .nft_list_len(Call::new(), gallery_id)
+            .map_err(|_| MinterError::InvalidParameter(InvalidParameter { point: 181 }))?;
+
+        // accepted nfts are indexed from 1
+        let mut rank = U256::from(1);
+        let mut nft_id = U256::from(1);
+        while nft_id <= accepted {
+            if self.get_stake(gallery_id, nft_id)? > stake {
+                rank += U256::from(1);
+            }
+            nft_id += U256::from(1);
+        }
+        Ok(rank)
+    }
+
     // this function will call the nft_submit contract and will set the metada to open
     pub fn set_libary(&mut self, s_nft_id: U256) -> Result<(), MinterError> {
         // this is to update the main nft libary and create the identification of the nft
@@ -240,18 +375,77 @@ impl Minter {
         let config = Call::new_in(self);
 
         // Attempt the transfer
-        meta_date_contract.mint(config, msg::sender(), new_nft_id, amount, vec![0]).map_err(|_e| {
+        meta_date_contract
+            .mint(config, msg::sender(), new_nft_id, amount, Vec::new().into())
+            .map_err(|_e| {
+                MinterError::InvalidParameter(InvalidParameter {
+                    point: 11,
+                })
+            })
+    }
+
+    // gives the creator the configured royalty on every resale of the nft
+    pub fn set_royalty(&mut self, token_id: U256, creator: Address) -> Result<(), MinterError> {
+        let royalty_bps = self.royalty_bps.get();
+        let meta_date_contract = IErc1155::new(*self.erc1155);
+        let config = Call::new_in(self);
+        meta_date_contract.set_royalty(config, token_id, creator, royalty_bps).map_err(|_e| {
             MinterError::InvalidParameter(InvalidParameter {
                 point: 11,
             })
         })
     }
 
-    pub fn set_data(
+    // mints a soulbound badge of the gallery; a badge already held is left as is.
+    // returns whether the badge was minted, failures are not reverted
+    pub fn mint_badge(&mut self, to: Address, kind: u8, gallery_id: U256) -> bool {
+        let meta_date_contract = IErc1155::new(*self.erc1155);
+        let config = Call::new_in(self);
+        meta_date_contract.mint_badge(config, to, kind, gallery_id).is_ok()
+    }
+
+    // records the voting window and result of the nft with the SFT; only the first claim does
+    pub fn set_provenance(
         &mut self,
         token_id: U256,
+        voting_start: u64,
+        voting_end: u64,
+        votes: U256,
+        rank: U256
+    ) -> Result<(), MinterError> {
+        let meta_date_contract = IErc1155::new(*self.erc1155);
+        let config = Call::new_in(self);
+        meta_date_contract
+            .set_provenance(config, token_id, voting_start, voting_end, votes, rank)
+            .map_err(|_e| {
+                MinterError::InvalidParameter(InvalidParameter {
+                    point: 11,
+                })
+            })
+    }
+
+    // caps the SFT at one edition; only the first claim of an nft sets it
+    pub fn set_max_supply(&mut self, token_id: U256) -> Result<(), MinterError> {
+        let meta_date_contract = IErc1155::new(*self.erc1155);
+        let config = Call::new_in(self);
+        meta_date_contract
+            .set_max_supply(config, token_id, U256::from(EDITION_SIZE))
+            .map_err(|_e| {
+                MinterError::InvalidParameter(InvalidParameter {
+                    point: 11,
+                })
+            })
+    }
+
+    // SFTs are minted under their nft_submit id, and nft_submit keeps the metadata of an nft
+    // under that same id (see `getNftData`), so the token id is also its metadata pointer
+    pub fn set_data(
+        &mut self,
+        nft_submit_id: U256,
         gallery_id: U256,
-        system_nft_id: U256
+        libary_nft_id: U256,
+        creator: Address,
+        vote_value: U256
     ) -> Result<(), MinterError> {
         let meta_date_contract = IErc1155::new(*self.erc1155);
 
@@ -260,7 +454,15 @@ impl Minter {
 
         // Attempt the transfer
         meta_date_contract
-            .set_data(config, token_id, gallery_id, system_nft_id, token_id)
+            .set_data(
+                config,
+                nft_submit_id,
+                gallery_id,
+                libary_nft_id,
+                nft_submit_id,
+                creator,
+                vote_value
+            )
             .map_err(|_e| {
                 MinterError::InvalidParameter(InvalidParameter {
                     point: 11,
@@ -51,6 +51,7 @@ sol_interface! {
         function getGallery(uint256 gallery_index) external view returns (address, string memory, string memory, uint32, uint64, uint256, uint64, uint64, uint256);
         function getLastIndex() external view returns (uint256);
         function getUserStatus(uint256 gallery_index, address user) external view returns (bool);
+        function isCancelled(uint256 gallery_index) external view returns (bool);
     }    
 }
 
@@ -242,7 +243,7 @@ impl Mainx {
         submit_address: Address,
         gallery_address: Address
     ) -> Result<(), NftError> {
-        self.check_admin().map_err(|e| { e })?;
+        self.check_admin()?;
         self.nft_submit.set(submit_address);
         self.gallery_c.set(gallery_address);
         Ok(())
@@ -302,8 +303,17 @@ impl Mainx {
         gallery_contract.get_user_status(config, gallery_index, user).expect("drat")
     }
 
+    // function to check if the gallery has been cancelled
+    pub fn is_cancelled(&self, gallery_index: U256) -> bool {
+        let address = self.gallery_c.get();
+        let gallery_contract = ISubject::new(address);
+        let config = Call::new();
+        gallery_contract.is_cancelled(config, gallery_index).expect("drat")
+    }
+
     // this will get the important gallery information from the gallery smart contract
     // creator address and the time the gallery is to start;
+    #[allow(clippy::result_unit_err)]
     pub fn get_gal_info(&self, gallery_index: U256) -> Result<(Address, u64), ()> {
         let address = self.gallery_c.get();
         let gallery_contract = ISubject::new(address);
@@ -324,22 +334,31 @@ impl Mainx {
             );
         }
 
+        // nfts can not be submitted to or reviewed in a cancelled gallery
+        if self.is_cancelled(gallery_id) {
+            return Err(
+                NftError::InvalidParameter(InvalidParameter {
+                    point: 12,
+                })
+            );
+        }
+
         Ok(true)
     }
 
     pub fn check_admin(&mut self) -> Result<bool, NftError> {
         let default_x = Address::from([0x00; 20]);
         if self.admin.get() != default_x && msg::sender() != self.admin.get() {
-            return Err(
+            Err(
                 NftError::InvalidParameter(InvalidParameter {
                     point: 17,
                 })
-            );
+            )
         } else if self.admin.get() == default_x {
             self.admin.set(msg::sender());
-            return Ok(true);
+            Ok(true)
         } else {
-            return Ok(true);
+            Ok(true)
         }
     }
 }
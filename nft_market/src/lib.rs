@@ -1,19 +1,24 @@
 // Allow `cargo stylus export-abi` to generate a main function.
-#![cfg_attr(not(feature = "export-abi"), no_main)]
+#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+// the permit entrypoints take more than seven arguments
+#![allow(clippy::too_many_arguments)]
 extern crate alloc;
 
 /// Import items from the SDK. The prelude contains common traits and macros.
 use stylus_sdk::{
-    alloy_primitives::{ U256, Address },
+    alloy_primitives::{ U256, Address, B256 },
     prelude::*,
     msg,
     evm,
     block,
-    call::Call,
+    call::{ call, Call },
     contract,
 };
 
-use alloy_sol_types::sol;
+use alloy_sol_types::{ sol, SolValue };
+
+// permit(address,address,uint256,uint256,uint8,bytes32,bytes32), called by hand (see the token's `permit`)
+const PERMIT: [u8; 4] = [0xd5, 0x05, 0xac, 0xcf];
 
 sol_storage! {
     #[entrypoint]
@@ -35,8 +40,10 @@ sol_storage! {
 sol_interface! {
     interface IErc1155 {
         function balanceOf(address account, uint256 id) external view returns (uint256);
-        function safeTransferFrom(address from, address to, uint256 id, uint256 amount, uint8[] memory data) external;
+        function safeTransferFrom(address from, address to, uint256 id, uint256 amount, bytes calldata data) external;
         function isApprovedForAll(address account, address operator) external view returns (bool);
+        function allowance(address account, address operator, uint256 id) external view returns (uint256);
+        function royaltyInfo(uint256 id, uint256 sale_price) external view returns (address, uint256);
     }
 
     
@@ -52,7 +59,7 @@ sol_interface! {
 
 sol! {
     // event to show that a new gallary have been created
-    event Sold(address indexed pre_owner, address indexed new_owner, uint256 indexed nft_id, uint64 time);
+    event Sold(address indexed pre_owner, address indexed new_owner, uint256 indexed nft_id, uint256 price, address royalty_receiver, uint256 royalty, uint64 time);
 
     
     // my error
@@ -141,7 +148,7 @@ impl Market {
         }
 
         // checking if the seller has given this contract the permission to transfer the nft
-        if !self.a_c(owner) {
+        if !self.a_c(owner, nft_id, amount) {
             return Err(
                 MarketError::NoAccess(NoAccess {
                     nft_id,
@@ -149,12 +156,20 @@ impl Market {
             );
         }
 
-        //transfer funds from the buyer to the seller
+        //transfer funds from the buyer to the seller, minus the creator's royalty
+        let price = Self::total_cost(cost, amount)?;
+        let (royalty_receiver, royalty) = self.r_i(nft_id, price, owner);
 
         self
-            .fund_tf(owner, cost * amount)
+            .fund_tf(owner, price - royalty)
             .map_err(|_| { MarketError::InSufficientAllowance(InSufficientAllowance { cost }) })?;
 
+        if royalty != U256::from(0) {
+            self
+                .fund_tf(royalty_receiver, royalty)
+                .map_err(|_| { MarketError::InSufficientAllowance(InSufficientAllowance { cost }) })?;
+        }
+
         // transfer nft from seller to buyer
         self
             .nft_tf(owner, nft_id, amount)
@@ -170,15 +185,34 @@ impl Market {
             pre_owner: owner,
             new_owner: msg::sender(),
             nft_id,
+            price,
+            royalty_receiver,
+            royalty,
             time: block::timestamp() as u64,
         });
 
         Ok(())
     }
 
+    // buys an nft using a signed permit for the total cost instead of a prior approve
+    pub fn buy_with_permit(
+        &mut self,
+        owner: Address,
+        nft_id: U256,
+        amount: U256,
+        deadline: U256,
+        v: u8,
+        r: B256,
+        s: B256
+    ) -> Result<(), MarketError> {
+        let cost = self.sales.getter(owner).getter(nft_id).price.get();
+        self.permit_tf(Self::total_cost(cost, amount)?, deadline, v, r, s);
+        self.buy(owner, nft_id, amount)
+    }
+
     // this function is used to check and set the erc1155 contract address by the admin
     pub fn set_erc1155(&mut self, erc1155: Address, erc20: Address) -> Result<(), MarketError> {
-        self.check_admin().map_err(|e| { e })?;
+        self.check_admin()?;
         self.erc1155.set(erc1155);
         self.erc20.set(erc20);
         Ok(())
@@ -196,13 +230,40 @@ impl Market {
     }
 
     // this function is used to check if the is smart contract has been approved for a transaction
-    pub fn a_c(&self, account: Address) -> bool {
+    // either for the whole collection or for at least `amount` of the nft
+    pub fn a_c(&self, account: Address, nft_id: U256, amount: U256) -> bool {
         let address = self.erc1155.get();
         let erc1155_contract = IErc1155::new(address);
-        let config = Call::new();
         // contract adderess
         let c_address = contract::address();
-        erc1155_contract.is_approved_for_all(config, account, c_address).expect("drat")
+        if erc1155_contract.is_approved_for_all(Call::new(), account, c_address).expect("drat") {
+            return true;
+        }
+        erc1155_contract.allowance(Call::new(), account, c_address, nft_id).expect("drat") >= amount
+    }
+
+    // this function gets the royalty owed on a sale of the nft at `price`
+    // returns (receiver, royalty); nothing is owed when the seller is the receiver
+    pub fn r_i(&self, nft_id: U256, price: U256, seller: Address) -> (Address, U256) {
+        let address = self.erc1155.get();
+        let erc1155_contract = IErc1155::new(address);
+        let config = Call::new();
+        let (receiver, royalty) = erc1155_contract
+            .royalty_info(config, nft_id, price)
+            .unwrap_or_default();
+        if receiver == Address::ZERO || receiver == seller || royalty > price {
+            return (Address::ZERO, U256::from(0));
+        }
+        (receiver, royalty)
+    }
+
+    // price of `amount` copies at `cost` each; reverts instead of wrapping
+    pub fn total_cost(cost: U256, amount: U256) -> Result<U256, MarketError> {
+        cost.checked_mul(amount).ok_or(
+            MarketError::InvalidParameter(InvalidParameter {
+                point: 2,
+            })
+        )
     }
 
     // This function handles the transfer of funds
@@ -214,7 +275,15 @@ impl Market {
         let config = Call::new_in(self);
 
         // Attempt the transfer
-        meta_date_contract.transfer_from(config, msg::sender(), owner, price).map_err(|_e| 0 as u8)
+        meta_date_contract.transfer_from(config, msg::sender(), owner, price).map_err(|_e| 0u8)
+    }
+
+    // submits the buyer's permit for the cost of a purchase; a failure is left to transfer_from (see the token's `permit`)
+    pub fn permit_tf(&mut self, value: U256, deadline: U256, v: u8, r: B256, s: B256) {
+        let args = (msg::sender(), contract::address(), value, deadline, U256::from(v), r, s)
+            .abi_encode_params();
+        let erc20 = *self.erc20;
+        let _ = call(Call::new_in(self), erc20, &[PERMIT.as_slice(), &args].concat());
     }
 
     // This function handles the transfer of nfts
@@ -227,8 +296,8 @@ impl Market {
 
         // Attempt the transfer
         meta_date_contract
-            .safe_transfer_from(config, from, msg::sender(), nft_id, amount, vec![0])
-            .map_err(|_e| 0 as u8)
+            .safe_transfer_from(config, from, msg::sender(), nft_id, amount, Vec::new().into())
+            .map_err(|_e| 0u8)
     }
 
     // this is the lock that lockes a user as the admin of this contract; there by making sure that it can only be called once
@@ -130,7 +130,7 @@ impl NftStorage {
         gallery: Address,
         minter: Address
     ) -> Result<(), SubmitError> {
-        self.check_admin().map_err(|e| { e })?;
+        self.check_admin()?;
         self.libary.set(libary_address);
         self.gallery_c.set(gallery);
         self.minter.set(minter);
@@ -0,0 +1,150 @@
+#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+extern crate alloc;
+
+use alloy_primitives::{ Address, I256, U64, U256 };
+use stylus_sdk::{ prelude::*, msg, evm, block, call::Call };
+use alloy_sol_types::sol;
+
+// decimals of the NovaV token priced by this oracle
+const TOKEN_DECIMALS: u8 = 10;
+
+sol_storage! {
+    // USD pricing of the NovaV sale through an ETH/USD feed
+    #[entrypoint]
+    pub struct Oracle {
+        address admin;
+
+        address price_feed; // ETH/USD aggregator with a Chainlink style latestRoundData
+        uint256 usd_price; // USD price of one whole token, 18 decimals
+        uint64 max_staleness; // seconds after its last update the feed is still trusted
+    }
+}
+
+sol_interface! {
+    // Chainlink style price feed, e.g. ETH/USD
+    interface IAggregator {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (uint80 round_id, int256 answer, uint256 started_at, uint256 updated_at, uint80 answered_in_round);
+    }
+}
+
+//  ======   Errors and Events  =========  //
+
+sol! {
+    event SetOracle(address indexed price_feed, uint256 usd_price, uint64 max_staleness, uint64 time);
+
+    // error to show invalid parameter
+    error InvalidParameter(uint8 point);
+
+    // the feed is unset, stale or answered something invalid
+    error FeedUnavailable(address price_feed);
+}
+
+#[derive(SolidityError)]
+pub enum OracleError {
+    InvalidParameter(InvalidParameter),
+    FeedUnavailable(FeedUnavailable),
+}
+
+#[public]
+impl Oracle {
+    // sets the ETH/USD feed, the USD price of one whole token (18 decimals) and how old,
+    // in seconds, the feed answer may be before it is no longer trusted
+    pub fn set_oracle(
+        &mut self,
+        price_feed: Address,
+        usd_price: U256,
+        max_staleness: u64
+    ) -> Result<(), OracleError> {
+        self.check_admin()?;
+        if price_feed == Address::ZERO || usd_price == U256::ZERO || max_staleness == 0 {
+            return Err(
+                OracleError::InvalidParameter(InvalidParameter {
+                    point: 1,
+                })
+            );
+        }
+
+        self.price_feed.set(price_feed);
+        self.usd_price.set(usd_price);
+        self.max_staleness.set(U64::from(max_staleness));
+        evm::log(SetOracle {
+            price_feed,
+            usd_price,
+            max_staleness,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    // returns (price_feed, usd_price, max_staleness)
+    pub fn get_oracle(&self) -> (Address, U256, u64) {
+        (self.price_feed.get(), self.usd_price.get(), self.max_staleness.get().to::<u64>())
+    }
+
+    // latest (answer, decimals) of the ETH/USD feed
+    pub fn eth_usd(&self) -> Result<(U256, U256), OracleError> {
+        let feed = self.price_feed.get();
+        let unavailable = || OracleError::FeedUnavailable(FeedUnavailable { price_feed: feed });
+        if feed == Address::ZERO {
+            return Err(unavailable());
+        }
+
+        let aggregator = IAggregator::new(feed);
+        let decimals = aggregator.decimals(Call::new()).map_err(|_e| unavailable())?;
+        let (round_id, answer, _started_at, updated_at, answered_in_round) = aggregator
+            .latest_round_data(Call::new())
+            .map_err(|_e| unavailable())?;
+
+        let max_staleness = U256::from(self.max_staleness.get().to::<u64>());
+        if
+            answer <= I256::ZERO ||
+            answered_in_round < round_id ||
+            updated_at > U256::from(block::timestamp()) ||
+            updated_at.saturating_add(max_staleness) < U256::from(block::timestamp())
+        {
+            return Err(unavailable());
+        }
+        Ok((answer.into_raw(), U256::from(decimals)))
+    }
+
+    // wei per token unit at `usd_price`; the token sells at this price in oracle mode
+    pub fn unit_price(&self) -> Result<U256, OracleError> {
+        let (answer, decimals) = self.eth_usd()?;
+        let overflow = || OracleError::InvalidParameter(InvalidParameter { point: 2 });
+        let unit = U256::from(10).pow(U256::from(TOKEN_DECIMALS));
+        let numerator = self.usd_price
+            .get()
+            .checked_mul(U256::from(10).pow(decimals))
+            .ok_or_else(overflow)?;
+        let denominator = answer.checked_mul(unit).ok_or_else(overflow)?;
+        Ok(numerator / denominator)
+    }
+
+    // USD value (18 decimals) of `wei`
+    pub fn quote_usd(&self, wei: U256) -> Result<U256, OracleError> {
+        let (answer, decimals) = self.eth_usd()?;
+        Ok(wei.saturating_mul(answer) / U256::from(10).pow(decimals))
+    }
+}
+
+// helper functions
+
+impl Oracle {
+    pub fn check_admin(&mut self) -> Result<bool, OracleError> {
+        let default_x = Address::from([0x00; 20]);
+        if self.admin.get() != default_x && msg::sender() != self.admin.get() {
+            return Err(
+                OracleError::InvalidParameter(InvalidParameter {
+                    point: 0,
+                })
+            );
+        } else if self.admin.get() == default_x {
+            self.admin.set(msg::sender());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests;
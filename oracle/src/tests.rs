@@ -0,0 +1,241 @@
+//! Unit tests run the contract against an in-memory stand-in of the Stylus host:
+//! storage lives in a per-thread map, the caller, block and contract address
+//! are the fixed values below, and the ETH/USD feed is answered from the
+//! per-thread `World` instead of being called.
+
+use super::*;
+
+// the account every call is made from; it becomes the admin on its first admin call
+const SENDER: Address = Address::new([0x11; 20]);
+const ORACLE: Address = Address::new([0x22; 20]);
+const FEED: Address = Address::new([0xfe; 20]);
+const NOW: u64 = 1_700_000_000;
+const BLOCK: u64 = 1_000;
+
+mod host {
+    use super::{ Address, BLOCK, I256, NOW, ORACLE, SENDER, U256 };
+    use alloy_sol_types::SolValue;
+    use sha3::{ Digest, Keccak256 };
+    use std::{ cell::RefCell, collections::HashMap };
+
+    // what the ETH/USD feed answers
+    #[derive(Default)]
+    pub struct World {
+        pub answer: I256,
+        pub updated_at: u64,
+        returned: Vec<u8>,
+    }
+
+    thread_local! {
+        static STORAGE: RefCell<HashMap<[u8; 32], [u8; 32]>> = RefCell::new(HashMap::new());
+        static WORLD: RefCell<World> = RefCell::new(World::default());
+    }
+
+    pub fn world<R>(f: impl FnOnce(&mut World) -> R) -> R {
+        WORLD.with(|w| f(&mut w.borrow_mut()))
+    }
+
+    pub fn selector(signature: &str) -> [u8; 4] {
+        Keccak256::digest(signature.as_bytes())[..4].try_into().unwrap()
+    }
+
+    // the return data of a call to the feed
+    fn answer(world: &World, calldata: &[u8]) -> Vec<u8> {
+        if calldata[..4] == selector("decimals()") {
+            U256::from(8).abi_encode()
+        } else {
+            // latestRoundData()
+            (U256::from(2), world.answer, U256::ZERO, U256::from(world.updated_at), U256::from(2)).abi_encode()
+        }
+    }
+
+    unsafe fn call(calldata: *const u8, calldata_len: usize) -> usize {
+        let calldata = std::slice::from_raw_parts(calldata, calldata_len);
+        world(|world| {
+            world.returned = answer(world, calldata);
+            world.returned.len()
+        })
+    }
+
+    unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+    }
+
+    unsafe fn address(dest: *mut u8, address: Address) {
+        write(dest, address.as_slice());
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = STORAGE.with(|s| s.borrow().get(&key).copied().unwrap_or_default());
+        write(dest, &value);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = *(value as *const [u8; 32]);
+        STORAGE.with(|s| s.borrow_mut().insert(key, value));
+    }
+
+    #[no_mangle]
+    extern "C" fn storage_flush_cache(_clear: bool) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+        let input = std::slice::from_raw_parts(bytes, len);
+        write(output, &Keccak256::digest(input));
+    }
+
+    #[no_mangle]
+    extern "C" fn emit_log(_data: *const u8, _len: usize, _topics: usize) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        address(sender, SENDER);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_value(value: *mut u8) {
+        write(value, &[0; 32]);
+    }
+
+    #[no_mangle]
+    extern "C" fn msg_reentrant() -> bool {
+        false
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn contract_address(dest: *mut u8) {
+        address(dest, ORACLE);
+    }
+
+    #[no_mangle]
+    extern "C" fn block_timestamp() -> u64 {
+        NOW
+    }
+
+    #[no_mangle]
+    extern "C" fn block_number() -> u64 {
+        BLOCK
+    }
+
+    #[no_mangle]
+    extern "C" fn chainid() -> u64 {
+        42161
+    }
+
+    #[no_mangle]
+    extern "C" fn account_code_size(_address: *const u8) -> usize {
+        0
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _value: *const u8,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn delegate_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn static_call_contract(
+        _contract: *const u8,
+        calldata: *const u8,
+        calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = call(calldata, calldata_len);
+        0
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn read_return_data(dest: *mut u8, offset: usize, size: usize) -> usize {
+        world(|world| {
+            let returned = world.returned.get(offset..).unwrap_or_default();
+            let size = size.min(returned.len());
+            write(dest, &returned[..size]);
+            size
+        })
+    }
+
+    #[no_mangle]
+    extern "C" fn return_data_size() -> usize {
+        world(|world| world.returned.len())
+    }
+}
+
+use host::world;
+
+// a fresh oracle; storage and the world are empty on every test thread
+fn oracle() -> Oracle {
+    unsafe { <Oracle as stylus_sdk::storage::StorageType>::new(U256::ZERO, 0) }
+}
+
+fn ok<T>(result: Result<T, OracleError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("call reverted: 0x{}", alloy_primitives::hex::encode(Vec::<u8>::from(e))),
+    }
+}
+
+// an oracle pricing one whole token at $0.50 against a feed answering $2_000 per ETH `age` seconds ago
+fn priced(age: u64) -> Oracle {
+    let mut oracle = oracle();
+    let usd_price = U256::from(5) * U256::from(10).pow(U256::from(17));
+    ok(oracle.set_oracle(FEED, usd_price, 3_600));
+    world(|world| {
+        world.answer = I256::try_from(2_000 * 100_000_000i64).unwrap();
+        world.updated_at = NOW - age;
+    });
+    oracle
+}
+
+#[test]
+fn unit_price_converts_the_usd_price_at_the_feed_answer() {
+    let oracle = priced(60);
+    // 0.5e18 * 1e8 / (2_000e8 * 1e10) = 25_000 wei per unit, 0.00025 ETH per token
+    assert_eq!(ok(oracle.unit_price()), U256::from(25_000));
+    // 0.00025 ETH at $2_000
+    let wei = U256::from(25_000) * U256::from(10).pow(U256::from(TOKEN_DECIMALS));
+    assert_eq!(ok(oracle.quote_usd(wei)), U256::from(5) * U256::from(10).pow(U256::from(17)));
+}
+
+#[test]
+fn stale_or_invalid_answers_revert() {
+    let oracle = priced(3_601);
+    assert!(matches!(oracle.unit_price(), Err(OracleError::FeedUnavailable(_))));
+
+    let oracle = priced(0);
+    world(|world| {
+        world.answer = I256::ZERO;
+    });
+    assert!(matches!(oracle.eth_usd(), Err(OracleError::FeedUnavailable(_))));
+}
+
+#[test]
+fn an_unset_feed_reverts() {
+    let mut oracle = oracle();
+    assert!(matches!(oracle.quote_usd(U256::from(1)), Err(OracleError::FeedUnavailable(_))));
+    let result = oracle.set_oracle(FEED, U256::ZERO, 3_600);
+    assert!(matches!(result, Err(OracleError::InvalidParameter(InvalidParameter { point: 1 }))));
+}
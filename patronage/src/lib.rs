@@ -0,0 +1,345 @@
+#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+extern crate alloc;
+
+use alloy_primitives::{ Address, U256, U64 };
+use stylus_sdk::{ prelude::*, msg, evm, block, contract };
+use alloy_sol_types::sol;
+
+use stylus_sdk::call::Call;
+
+sol_storage! {
+    #[entrypoint]
+    pub struct Patronage {
+        address erc20;
+        address admin;
+
+        // starting index from 1 to reduce paralax error
+        uint256 stream_count;
+        mapping(uint256 => Stream) streams;
+
+        // ids of the streams each patron funds and each creator receives
+        mapping(address => StreamList) patron_streams;
+        mapping(address => StreamList) creator_streams;
+    }
+
+    // NovaV held by this contract and released to the creator every second
+    // between `start` and `stop`; a cancelled stream stops at the time it was cancelled
+    pub struct Stream {
+        address patron;
+        address creator;
+        uint256 deposit; // rate * (stop - start)
+        uint256 rate; // tokens per second
+        uint64 start;
+        uint64 stop;
+        uint256 withdrawn; // tokens already paid to the creator
+        bool cancelled;
+        uint256 locked; // part of the deposit paid with locked (vesting) tokens
+    }
+
+    pub struct StreamList {
+        uint256[] ids;
+    }
+}
+
+sol_interface! {
+    interface IErc20 {
+        function transferFrom(address from, address to, uint256 value) external returns (bool);
+        function transfer(address to, uint256 value) external returns (bool);
+        function platformLockedOf(address platform, address account) external view returns (uint256);
+        function refundLocked(address to, uint256 value, uint256 locked) external returns (uint256);
+    }
+}
+
+//  ======   Errors and Events  =========  //
+
+sol! {
+    event StreamCreated(uint256 indexed stream_id, address indexed patron, address indexed creator, uint256 rate, uint256 deposit, uint64 start, uint64 stop, uint64 time);
+
+    event StreamWithdrawn(uint256 indexed stream_id, address indexed creator, uint256 amount, uint64 time);
+
+    event StreamCancelled(uint256 indexed stream_id, address indexed patron, address indexed creator, uint256 creator_amount, uint256 patron_refund, uint64 time);
+
+    // error to show invalid parameter
+    error InvalidParameter(uint8 point);
+
+    error NoData();
+
+    error StreamClosed(uint256 stream_id);
+    error InSufficientAllowance(uint256 value);
+}
+
+#[derive(SolidityError)]
+pub enum PatronError {
+    InvalidParameter(InvalidParameter),
+    NoData(NoData),
+    StreamClosed(StreamClosed),
+    InSufficientAllowance(InSufficientAllowance),
+}
+
+#[public]
+impl Patronage {
+    // streams `rate` tokens per second to `creator` for `duration` seconds, starting now;
+    // the whole deposit (rate * duration) is taken from the patron up front
+    pub fn create_stream(
+        &mut self,
+        creator: Address,
+        rate: U256,
+        duration: u64
+    ) -> Result<U256, PatronError> {
+        let patron = msg::sender();
+        if creator == Address::ZERO || creator == patron || rate == U256::ZERO || duration == 0 {
+            return Err(
+                PatronError::InvalidParameter(InvalidParameter {
+                    point: 1,
+                })
+            );
+        }
+
+        let deposit = rate.checked_mul(U256::from(duration)).ok_or(
+            PatronError::InvalidParameter(InvalidParameter {
+                point: 2,
+            })
+        )?;
+
+        let paid_in = self.locked_paid_in(patron);
+        self.fund_tf(patron, deposit).map_err(|_| {
+            PatronError::InSufficientAllowance(InSufficientAllowance { value: deposit })
+        })?;
+        let locked = self.locked_paid_in(patron).saturating_sub(paid_in);
+
+        let start = block::timestamp();
+        let stop = start + duration;
+        let stream_id = self.stream_count.get() + U256::from(1);
+
+        let mut stream = self.streams.setter(stream_id);
+        stream.patron.set(patron);
+        stream.creator.set(creator);
+        stream.deposit.set(deposit);
+        stream.rate.set(rate);
+        stream.start.set(U64::from(start));
+        stream.stop.set(U64::from(stop));
+        stream.locked.set(locked);
+        self.stream_count.set(stream_id);
+
+        self.patron_streams.setter(patron).ids.push(stream_id);
+        self.creator_streams.setter(creator).ids.push(stream_id);
+
+        evm::log(StreamCreated {
+            stream_id,
+            patron,
+            creator,
+            rate,
+            deposit,
+            start,
+            stop,
+            time: start,
+        });
+        Ok(stream_id)
+    }
+
+    // pays the creator everything the stream has accrued so far
+    pub fn withdraw(&mut self, stream_id: U256) -> Result<U256, PatronError> {
+        let creator = msg::sender();
+        if self.streams.getter(stream_id).creator.get() != creator {
+            return Err(
+                PatronError::InvalidParameter(InvalidParameter {
+                    point: 3,
+                })
+            );
+        }
+
+        let (amount, _) = self.balances(stream_id);
+        if amount == U256::ZERO {
+            return Err(PatronError::NoData(NoData {}));
+        }
+
+        let mut stream = self.streams.setter(stream_id);
+        let withdrawn = stream.withdrawn.get();
+        stream.withdrawn.set(withdrawn + amount);
+
+        self.fund_fw(creator, amount).map_err(|_| {
+            PatronError::InvalidParameter(InvalidParameter {
+                point: 4,
+            })
+        })?;
+
+        evm::log(StreamWithdrawn {
+            stream_id,
+            creator,
+            amount,
+            time: block::timestamp() as u64,
+        });
+        Ok(amount)
+    }
+
+    // stops the stream; the creator is paid what has accrued and the patron gets the rest back,
+    // with the part paid with locked tokens going back under the patron's lock
+    // (callable by either the patron or the creator)
+    pub fn cancel(&mut self, stream_id: U256) -> Result<(), PatronError> {
+        let stream = self.streams.getter(stream_id);
+        let patron = stream.patron.get();
+        let creator = stream.creator.get();
+        if msg::sender() != patron && msg::sender() != creator {
+            return Err(
+                PatronError::InvalidParameter(InvalidParameter {
+                    point: 5,
+                })
+            );
+        }
+        if stream.cancelled.get() || block::timestamp() >= stream.stop.get().to::<u64>() {
+            return Err(PatronError::StreamClosed(StreamClosed { stream_id }));
+        }
+
+        let (creator_amount, patron_refund) = self.balances(stream_id);
+        let locked = stream.locked.get().min(patron_refund);
+
+        let time = block::timestamp();
+        let mut stream = self.streams.setter(stream_id);
+        let withdrawn = stream.withdrawn.get();
+        stream.withdrawn.set(withdrawn + creator_amount);
+        stream.stop.set(U64::from(time));
+        stream.cancelled.set(true);
+
+        if creator_amount != U256::ZERO {
+            self.fund_fw(creator, creator_amount).map_err(|_| {
+                PatronError::InvalidParameter(InvalidParameter {
+                    point: 6,
+                })
+            })?;
+        }
+        if patron_refund != U256::ZERO {
+            self.refund_fw(patron, patron_refund, locked).map_err(|_| {
+                PatronError::InvalidParameter(InvalidParameter {
+                    point: 7,
+                })
+            })?;
+        }
+
+        evm::log(StreamCancelled {
+            stream_id,
+            patron,
+            creator,
+            creator_amount,
+            patron_refund,
+            time,
+        });
+        Ok(())
+    }
+
+    // returns (patron, creator, deposit, rate, start, stop, withdrawn, cancelled)
+    #[allow(clippy::type_complexity)]
+    pub fn get_stream(
+        &self,
+        stream_id: U256
+    ) -> Result<(Address, Address, U256, U256, u64, u64, U256, bool), PatronError> {
+        let stream = self.streams.getter(stream_id);
+        if stream.patron.get() == Address::ZERO {
+            return Err(PatronError::NoData(NoData {}));
+        }
+
+        Ok((
+            stream.patron.get(),
+            stream.creator.get(),
+            stream.deposit.get(),
+            stream.rate.get(),
+            stream.start.get().to::<u64>(),
+            stream.stop.get().to::<u64>(),
+            stream.withdrawn.get(),
+            stream.cancelled.get(),
+        ))
+    }
+
+    // returns (amount the creator can withdraw now, amount the patron would get back on cancel)
+    pub fn balance_of_stream(&self, stream_id: U256) -> (U256, U256) {
+        self.balances(stream_id)
+    }
+
+    // ids of the streams funded by `patron`
+    pub fn streams_of_patron(&self, patron: Address) -> Vec<U256> {
+        let list = self.patron_streams.getter(patron);
+        (0..list.ids.len()).filter_map(|i| list.ids.get(i)).collect()
+    }
+
+    // ids of the streams paying `creator`
+    pub fn streams_of_creator(&self, creator: Address) -> Vec<U256> {
+        let list = self.creator_streams.getter(creator);
+        (0..list.ids.len()).filter_map(|i| list.ids.get(i)).collect()
+    }
+
+    pub fn get_stream_count(&self) -> U256 {
+        self.stream_count.get()
+    }
+
+    pub fn set_erc20(&mut self, erc20_address: Address) -> Result<(), PatronError> {
+        self.check_admin()?;
+        self.erc20.set(erc20_address);
+        Ok(())
+    }
+}
+
+// helper functions
+
+impl Patronage {
+    // splits what is left of the deposit between the creator (accrued, not yet withdrawn)
+    // and the patron (not yet accrued)
+    pub fn balances(&self, stream_id: U256) -> (U256, U256) {
+        let stream = self.streams.getter(stream_id);
+        if stream.cancelled.get() {
+            return (U256::ZERO, U256::ZERO);
+        }
+
+        let start = stream.start.get().to::<u64>();
+        let stop = stream.stop.get().to::<u64>();
+        let now = block::timestamp().clamp(start, stop);
+
+        let accrued = stream.rate.get() * U256::from(now - start);
+        (accrued - stream.withdrawn.get(), stream.deposit.get() - accrued)
+    }
+
+    // This function takes the deposit from the patron
+    pub fn fund_tf(&mut self, patron: Address, value: U256) -> Result<bool, u8> {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract
+            .transfer_from(config, patron, contract::address(), value)
+            .map_err(|_e| 0u8)
+    }
+
+    // this function sends tokens held by this contract to `to`
+    pub fn fund_fw(&mut self, to: Address, amount: U256) -> Result<bool, u8> {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract.transfer(config, to, amount).map_err(|_e| 0u8)
+    }
+
+    // this function refunds `amount` to `to`, putting up to `locked` of it back under their lock
+    pub fn refund_fw(&mut self, to: Address, amount: U256, locked: U256) -> Result<bool, u8> {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract
+            .refund_locked(config, to, amount, locked)
+            .map(|_relocked| true)
+            .map_err(|_e| 0u8)
+    }
+
+    // locked tokens of `patron` the token has recorded as paid into this contract and not refunded
+    pub fn locked_paid_in(&self, patron: Address) -> U256 {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new();
+        meta_date_contract.platform_locked_of(config, contract::address(), patron).expect("drat")
+    }
+
+    pub fn check_admin(&mut self) -> Result<bool, PatronError> {
+        let default_x = Address::from([0x00; 20]);
+        if self.admin.get() != default_x && msg::sender() != self.admin.get() {
+            return Err(
+                PatronError::InvalidParameter(InvalidParameter {
+                    point: 9,
+                })
+            );
+        } else if self.admin.get() == default_x {
+            self.admin.set(msg::sender());
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,309 @@
+#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+extern crate alloc;
+
+use alloy_primitives::{ Address, U256 };
+use stylus_sdk::{ prelude::*, msg, evm, block, contract, call::{ call, Call } };
+use alloy_sol_types::sol;
+
+const BPS: u64 = 10_000;
+
+sol_storage! {
+    // NovaV/ETH constant product pool; the pooled NovaV is held by this contract on the token
+    #[entrypoint]
+    pub struct Pool {
+        address erc20;
+        address admin;
+
+        uint256 pool_token; // NovaV in the pool
+        uint256 pool_eth; // wei in the pool
+        uint256 swap_fee_bps; // fee taken from the input of every swap
+    }
+}
+
+sol_interface! {
+    interface IErc20 {
+        function transferFrom(address from, address to, uint256 value) external returns (bool);
+        function transfer(address to, uint256 value) external returns (bool);
+    }
+}
+
+//  ======   Errors and Events  =========  //
+
+sol! {
+    event Swap(address indexed sender, uint256 eth_in, uint256 token_in, uint256 eth_out, uint256 token_out, uint64 time);
+
+    // the reserves after every change
+    event Sync(uint256 reserve_token, uint256 reserve_eth);
+
+    event SetSwapFee(uint256 old_fee_bps, uint256 new_fee_bps, uint64 time);
+
+    // error to show invalid parameter
+    error InvalidParameter(uint8 point);
+
+    error InsufficientOutput(uint256 value, uint256 min_value);
+    error InsufficientLiquidity(uint256 reserve_token, uint256 reserve_eth);
+}
+
+#[derive(SolidityError)]
+pub enum PoolError {
+    InvalidParameter(InvalidParameter),
+    InsufficientOutput(InsufficientOutput),
+    InsufficientLiquidity(InsufficientLiquidity),
+}
+
+#[public]
+impl Pool {
+    // adds `token_amount` of the admin's tokens and the sent ETH to the pool;
+    // the admin approves this contract for the tokens first
+    #[payable]
+    pub fn add_liquidity(&mut self, token_amount: U256) -> Result<(), PoolError> {
+        self.check_admin()?;
+        self.fund_tf(msg::sender(), token_amount).map_err(|_| {
+            PoolError::InvalidParameter(InvalidParameter {
+                point: 4,
+            })
+        })?;
+        self.pool_token.set(self.pool_token.get() + token_amount);
+        self.pool_eth.set(self.pool_eth.get() + msg::value());
+        self.sync();
+        Ok(())
+    }
+
+    // takes `token_amount` tokens and `eth_amount` wei out of the pool back to the admin
+    pub fn remove_liquidity(&mut self, token_amount: U256, eth_amount: U256) -> Result<(), PoolError> {
+        self.check_admin()?;
+        let (reserve_token, reserve_eth) = (self.pool_token.get(), self.pool_eth.get());
+        if token_amount > reserve_token || eth_amount > reserve_eth {
+            return Err(
+                PoolError::InsufficientLiquidity(InsufficientLiquidity {
+                    reserve_token,
+                    reserve_eth,
+                })
+            );
+        }
+
+        self.pool_token.set(reserve_token - token_amount);
+        self.pool_eth.set(reserve_eth - eth_amount);
+        if token_amount > U256::ZERO {
+            self.fund_fw(msg::sender(), token_amount).map_err(|_| {
+                PoolError::InvalidParameter(InvalidParameter {
+                    point: 4,
+                })
+            })?;
+        }
+        if eth_amount > U256::ZERO {
+            self.send_eth(msg::sender(), eth_amount)?;
+        }
+        self.sync();
+        Ok(())
+    }
+
+    // sets the fee taken from the input of every swap, in basis points
+    pub fn set_swap_fee(&mut self, fee_bps: U256) -> Result<(), PoolError> {
+        self.check_admin()?;
+        if fee_bps >= U256::from(BPS) {
+            return Err(
+                PoolError::InvalidParameter(InvalidParameter {
+                    point: 1,
+                })
+            );
+        }
+
+        let old_fee_bps = self.swap_fee_bps.get();
+        self.swap_fee_bps.set(fee_bps);
+        evm::log(SetSwapFee {
+            old_fee_bps,
+            new_fee_bps: fee_bps,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    // returns (NovaV reserve, wei reserve, fee in bps)
+    pub fn get_pool(&self) -> (U256, U256, U256) {
+        (self.pool_token.get(), self.pool_eth.get(), self.swap_fee_bps.get())
+    }
+
+    // output of swapping `amount_in`; ETH for NovaV when `eth_in` is set, NovaV for ETH otherwise
+    pub fn get_amount_out(&self, amount_in: U256, eth_in: bool) -> Result<U256, PoolError> {
+        let (reserve_in, reserve_out) = self.pool_sides(eth_in)?;
+        let overflow = || PoolError::InvalidParameter(InvalidParameter { point: 3 });
+        let fee = U256::from(BPS) - self.swap_fee_bps.get();
+        let amount_in_with_fee = amount_in.checked_mul(fee).ok_or_else(overflow)?;
+        let numerator = amount_in_with_fee.checked_mul(reserve_out).ok_or_else(overflow)?;
+        let denominator = reserve_in
+            .checked_mul(U256::from(BPS))
+            .and_then(|x| x.checked_add(amount_in_with_fee))
+            .ok_or_else(overflow)?;
+        Ok(numerator / denominator)
+    }
+
+    // input needed to get `amount_out` out of a swap; ETH for NovaV when `eth_in` is set, NovaV for ETH otherwise
+    pub fn get_amount_in(&self, amount_out: U256, eth_in: bool) -> Result<U256, PoolError> {
+        let (reserve_in, reserve_out) = self.pool_sides(eth_in)?;
+        if amount_out >= reserve_out {
+            return Err(
+                PoolError::InsufficientLiquidity(InsufficientLiquidity {
+                    reserve_token: self.pool_token.get(),
+                    reserve_eth: self.pool_eth.get(),
+                })
+            );
+        }
+
+        let overflow = || PoolError::InvalidParameter(InvalidParameter { point: 3 });
+        let fee = U256::from(BPS) - self.swap_fee_bps.get();
+        let numerator = reserve_in
+            .checked_mul(amount_out)
+            .and_then(|x| x.checked_mul(U256::from(BPS)))
+            .ok_or_else(overflow)?;
+        let denominator = (reserve_out - amount_out).checked_mul(fee).ok_or_else(overflow)?;
+        Ok(numerator / denominator + U256::from(1))
+    }
+
+    // swaps the sent ETH for at least `min_out` NovaV from the pool
+    #[payable]
+    pub fn swap_eth_for_tokens(&mut self, min_out: U256) -> Result<(), PoolError> {
+        let eth_in = msg::value();
+        let token_out = self.get_amount_out(eth_in, true)?;
+        if eth_in == U256::ZERO || token_out == U256::ZERO || token_out < min_out {
+            return Err(
+                PoolError::InsufficientOutput(InsufficientOutput {
+                    value: token_out,
+                    min_value: min_out,
+                })
+            );
+        }
+
+        self.pool_eth.set(self.pool_eth.get() + eth_in);
+        self.pool_token.set(self.pool_token.get() - token_out);
+        self.fund_fw(msg::sender(), token_out).map_err(|_| {
+            PoolError::InvalidParameter(InvalidParameter {
+                point: 4,
+            })
+        })?;
+
+        evm::log(Swap {
+            sender: msg::sender(),
+            eth_in,
+            token_in: U256::ZERO,
+            eth_out: U256::ZERO,
+            token_out,
+            time: block::timestamp() as u64,
+        });
+        self.sync();
+        Ok(())
+    }
+
+    // swaps `token_in` NovaV of the caller for at least `min_out` wei from the pool;
+    // the caller approves this contract for the tokens first
+    pub fn swap_tokens_for_eth(&mut self, token_in: U256, min_out: U256) -> Result<(), PoolError> {
+        let eth_out = self.get_amount_out(token_in, false)?;
+        if token_in == U256::ZERO || eth_out == U256::ZERO || eth_out < min_out {
+            return Err(
+                PoolError::InsufficientOutput(InsufficientOutput {
+                    value: eth_out,
+                    min_value: min_out,
+                })
+            );
+        }
+
+        self.fund_tf(msg::sender(), token_in).map_err(|_| {
+            PoolError::InvalidParameter(InvalidParameter {
+                point: 4,
+            })
+        })?;
+        self.pool_token.set(self.pool_token.get() + token_in);
+        self.pool_eth.set(self.pool_eth.get() - eth_out);
+        self.send_eth(msg::sender(), eth_out)?;
+
+        evm::log(Swap {
+            sender: msg::sender(),
+            eth_in: U256::ZERO,
+            token_in,
+            eth_out,
+            token_out: U256::ZERO,
+            time: block::timestamp() as u64,
+        });
+        self.sync();
+        Ok(())
+    }
+
+    pub fn set_erc20(&mut self, erc20_address: Address) -> Result<(), PoolError> {
+        self.check_admin()?;
+        self.erc20.set(erc20_address);
+        Ok(())
+    }
+}
+
+// helper functions
+
+impl Pool {
+    // pool reserves ordered as (input side, output side) of a swap
+    pub fn pool_sides(&self, eth_in: bool) -> Result<(U256, U256), PoolError> {
+        let (reserve_token, reserve_eth) = (self.pool_token.get(), self.pool_eth.get());
+        if reserve_token == U256::ZERO || reserve_eth == U256::ZERO {
+            return Err(
+                PoolError::InsufficientLiquidity(InsufficientLiquidity {
+                    reserve_token,
+                    reserve_eth,
+                })
+            );
+        }
+
+        if eth_in {
+            return Ok((reserve_eth, reserve_token));
+        }
+        Ok((reserve_token, reserve_eth))
+    }
+
+    // emits the current reserves of the pool
+    pub fn sync(&self) {
+        evm::log(Sync {
+            reserve_token: self.pool_token.get(),
+            reserve_eth: self.pool_eth.get(),
+        });
+    }
+
+    // This function takes `value` tokens from `from` into the pool
+    pub fn fund_tf(&mut self, from: Address, value: U256) -> Result<bool, u8> {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract
+            .transfer_from(config, from, contract::address(), value)
+            .map_err(|_e| 0u8)
+    }
+
+    // this function sends tokens held by this contract to `to`
+    pub fn fund_fw(&mut self, to: Address, amount: U256) -> Result<bool, u8> {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract.transfer(config, to, amount).map_err(|_e| 0u8)
+    }
+
+    // sends `amount` wei held by the pool to `to`
+    pub fn send_eth(&mut self, to: Address, amount: U256) -> Result<(), PoolError> {
+        call(Call::new_in(self).value(amount), to, &[]).map_err(|_e| {
+            PoolError::InvalidParameter(InvalidParameter {
+                point: 2,
+            })
+        })?;
+        Ok(())
+    }
+
+    pub fn check_admin(&mut self) -> Result<bool, PoolError> {
+        let default_x = Address::from([0x00; 20]);
+        if self.admin.get() != default_x && msg::sender() != self.admin.get() {
+            return Err(
+                PoolError::InvalidParameter(InvalidParameter {
+                    point: 0,
+                })
+            );
+        } else if self.admin.get() == default_x {
+            self.admin.set(msg::sender());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,208 @@
+//! Unit tests run the contract against an in-memory stand-in of the Stylus host:
+//! storage lives in a per-thread map, the caller, block and contract address
+//! are the fixed values below, and there are no other contracts to call.
+
+use super::*;
+
+// the account every call is made from; it becomes the admin on its first admin call
+const SENDER: Address = Address::new([0x11; 20]);
+const POOL: Address = Address::new([0x22; 20]);
+const NOW: u64 = 1_700_000_000;
+const BLOCK: u64 = 1_000;
+
+mod host {
+    use super::{ Address, BLOCK, NOW, POOL, SENDER };
+    use sha3::{ Digest, Keccak256 };
+    use std::{ cell::RefCell, collections::HashMap };
+
+    thread_local! {
+        static STORAGE: RefCell<HashMap<[u8; 32], [u8; 32]>> = RefCell::new(HashMap::new());
+    }
+
+    unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+    }
+
+    unsafe fn address(dest: *mut u8, address: Address) {
+        write(dest, address.as_slice());
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = STORAGE.with(|s| s.borrow().get(&key).copied().unwrap_or_default());
+        write(dest, &value);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = *(value as *const [u8; 32]);
+        STORAGE.with(|s| s.borrow_mut().insert(key, value));
+    }
+
+    #[no_mangle]
+    extern "C" fn storage_flush_cache(_clear: bool) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+        let input = std::slice::from_raw_parts(bytes, len);
+        write(output, &Keccak256::digest(input));
+    }
+
+    #[no_mangle]
+    extern "C" fn emit_log(_data: *const u8, _len: usize, _topics: usize) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        address(sender, SENDER);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_value(value: *mut u8) {
+        write(value, &[0; 32]);
+    }
+
+    #[no_mangle]
+    extern "C" fn msg_reentrant() -> bool {
+        false
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn contract_address(dest: *mut u8) {
+        address(dest, POOL);
+    }
+
+    #[no_mangle]
+    extern "C" fn block_timestamp() -> u64 {
+        NOW
+    }
+
+    #[no_mangle]
+    extern "C" fn block_number() -> u64 {
+        BLOCK
+    }
+
+    #[no_mangle]
+    extern "C" fn chainid() -> u64 {
+        42161
+    }
+
+    #[no_mangle]
+    extern "C" fn account_code_size(_address: *const u8) -> usize {
+        0
+    }
+
+    // there are no other contracts: every call reverts without return data
+    #[no_mangle]
+    unsafe extern "C" fn call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _value: *const u8,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn delegate_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn static_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    extern "C" fn read_return_data(_dest: *mut u8, _offset: usize, _size: usize) -> usize {
+        0
+    }
+
+    #[no_mangle]
+    extern "C" fn return_data_size() -> usize {
+        0
+    }
+}
+
+
+// a fresh pool; storage is empty on every test thread
+fn pool() -> Pool {
+    unsafe { <Pool as stylus_sdk::storage::StorageType>::new(U256::ZERO, 0) }
+}
+
+fn ok<T>(result: Result<T, PoolError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("call reverted: 0x{}", alloy_primitives::hex::encode(Vec::<u8>::from(e))),
+    }
+}
+
+fn tokens(amount: u64) -> U256 {
+    U256::from(amount)
+}
+
+// a pool of 1_000_000 NovaV units against 1_000 wei with the usual 0.3% fee
+fn filled() -> Pool {
+    let mut pool = pool();
+    pool.pool_token.set(tokens(1_000_000));
+    pool.pool_eth.set(tokens(1_000));
+    pool.swap_fee_bps.set(tokens(30));
+    pool
+}
+
+#[test]
+fn swap_output_follows_the_constant_product_after_the_fee() {
+    let pool = filled();
+    // 100 * 9_970 * 1_000_000 / (1_000 * 10_000 + 100 * 9_970)
+    assert_eq!(ok(pool.get_amount_out(tokens(100), true)), tokens(90_661));
+    // 1_000 * 9_970 * 1_000 / (1_000_000 * 10_000 + 1_000 * 9_970)
+    assert_eq!(ok(pool.get_amount_out(tokens(1_000), false)), tokens(0));
+    assert_eq!(ok(pool.get_amount_out(tokens(10_000), false)), tokens(9));
+}
+
+#[test]
+fn swap_input_is_rounded_up_to_cover_the_output() {
+    let pool = filled();
+    let amount_in = ok(pool.get_amount_in(tokens(90_000), true));
+    assert_eq!(amount_in, tokens(100));
+    assert!(ok(pool.get_amount_out(amount_in, true)) >= tokens(90_000));
+
+    let drained = pool.get_amount_in(tokens(1_000_000), true);
+    assert!(matches!(drained, Err(PoolError::InsufficientLiquidity(_))));
+}
+
+#[test]
+fn swap_quotes_revert_instead_of_wrapping() {
+    let mut pool = filled();
+    let out = pool.get_amount_out(U256::MAX / U256::from(2), true);
+    assert!(matches!(out, Err(PoolError::InvalidParameter(InvalidParameter { point: 3 }))));
+
+    pool.pool_eth.set(U256::MAX / U256::from(2));
+    let quote = pool.get_amount_in(tokens(999_999), true);
+    assert!(matches!(quote, Err(PoolError::InvalidParameter(InvalidParameter { point: 3 }))));
+}
+
+#[test]
+fn an_empty_pool_quotes_nothing() {
+    let pool = pool();
+    let out = pool.get_amount_out(tokens(100), true);
+    assert!(matches!(out, Err(PoolError::InsufficientLiquidity(_))));
+}
@@ -1,11 +1,23 @@
 #![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+// the permit entrypoints take more than seven arguments
+#![allow(clippy::too_many_arguments)]
 extern crate alloc;
 
-use alloy_primitives::{ Address, U256 };
-use stylus_sdk::{ prelude::*, msg, block };
-use alloy_sol_types::sol;
+use alloy_primitives::{ Address, B256, FixedBytes, U256 };
+use stylus_sdk::{ prelude::*, abi::Bytes, msg, evm, block, contract };
+use alloy_sol_types::{ sol, SolValue };
 
-use stylus_sdk::call::Call;
+use stylus_sdk::call::{ call, Call };
+
+// permit(address,address,uint256,uint256,uint8,bytes32,bytes32), called by hand (see the token's `permit`)
+const PERMIT: [u8; 4] = [0xd5, 0x05, 0xac, 0xcf];
+
+// selector returned to the token when a vote is paid with transferAndCall
+const ON_TRANSFER_RECEIVED: [u8; 4] = [0x88, 0xa7, 0xca, 0x5c];
+
+// actions that can be encoded in the first word of the transferAndCall data
+const ACTION_CAST: u8 = 0; // (action, gallery_id, nft_id); the tokens sent are the bid
+const ACTION_INCREASE: u8 = 1; // (action, gallery_id, nft_id, vote_id); the tokens sent are added to the bid
 
 // this contract is the safe contract allowed to take the user votes
 sol_storage! {
@@ -16,6 +28,7 @@ sol_storage! {
         address nft_libary; // stores the nft_libary contract address
         address erc20; // stores the token contract address
         address gallery_c; // stores the gallery contract address
+        mapping(address => uint256) earnings; // votes held by this contract that the nft creators can withdraw
     }
 }
 
@@ -23,12 +36,14 @@ sol_interface! {
     // interface of the token contract
     interface IErc20 {
         function transferFrom(address from, address to, uint256 value) external returns (bool);
+        function transfer(address to, uint256 value) external returns (bool);
     }
 
     // interface of the gallery contract
     interface ISubject {
         function getGallery(uint256 gallery_index) external view returns (address, string memory, string memory, uint32, uint64, uint256, uint64, uint64, uint256);
         function getUserStatus(uint256 gallery_index, address user) external view returns (bool);
+        function isCancelled(uint256 gallery_index) external view returns (bool);
     }    
 
     // interface of the unsafe stake contract
@@ -47,6 +62,8 @@ sol_interface! {
 
 sol! {
     
+        event EarningsWithdrawn(address indexed creator, uint256 amount, uint64 time);
+
         error InvalidParameter(uint8 point);
         error InvalidGallery(uint8 point);
         error InvalidState(uint8 point);
@@ -76,6 +93,159 @@ impl Cast {
         nft_id: U256,
         bid: U256
     ) -> Result<(), CastError> {
+        self.cast(msg::sender(), gallery_id, nft_id, bid, false)
+    }
+
+    // increse the value of the casted vote
+    pub fn increase_cast(
+        &mut self,
+        gallery_id: U256, // gallery identity
+        nft_id: U256, //nft identity
+        vote_id: U256, // cast id
+        bid: U256 // new bid value
+    ) -> Result<(), CastError> {
+        self.increase(msg::sender(), gallery_id, nft_id, vote_id, bid, false)
+    }
+
+    // ERC-1363 hook: casts or increases a vote with tokens sent through the token's transferAndCall
+    // the action and its ids are encoded in `data` as 32 byte words (see ACTION_CAST and ACTION_INCREASE)
+    // the token can not be called back from here, so the tokens are credited to the nft creator to withdraw
+    pub fn on_transfer_received(
+        &mut self,
+        _operator: Address,
+        from: Address,
+        value: U256,
+        data: Bytes
+    ) -> Result<FixedBytes<4>, CastError> {
+        if msg::sender() != self.erc20.get() {
+            return Err(
+                CastError::InvalidParameter(InvalidParameter {
+                    point: 10,
+                })
+            );
+        }
+
+        let word = |i: usize| {
+            data.get(i * 32..(i + 1) * 32)
+                .map(U256::from_be_slice)
+                .ok_or(CastError::InvalidParameter(InvalidParameter { point: 12 }))
+        };
+
+        let action = word(0)?;
+        if action == U256::from(ACTION_CAST) {
+            self.cast(from, word(1)?, word(2)?, value, true)?;
+        } else if action == U256::from(ACTION_INCREASE) {
+            let (gallery_id, nft_id, vote_id) = (word(1)?, word(2)?, word(3)?);
+            let old_bid = self.get_staking_data(gallery_id, nft_id, vote_id, from)?;
+            self.increase(from, gallery_id, nft_id, vote_id, old_bid + value, true)?;
+        } else {
+            return Err(
+                CastError::InvalidParameter(InvalidParameter {
+                    point: 13,
+                })
+            );
+        }
+
+        Ok(FixedBytes(ON_TRANSFER_RECEIVED))
+    }
+
+    // lets an nft creator withdraw the votes paid for their nfts
+    pub fn withdraw_earnings(&mut self) -> Result<(), CastError> {
+        let creator = msg::sender();
+        let amount = self.earnings.get(creator);
+        if amount == U256::from(0) {
+            return Err(
+                CastError::InvalidCreatorState(InvalidCreatorState {
+                    point: 14,
+                })
+            );
+        }
+
+        self.earnings.setter(creator).set(U256::from(0));
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract.transfer(config, creator, amount).map_err(|_e| {
+            CastError::InvalidParameter(InvalidParameter {
+                point: 201,
+            })
+        })?;
+
+        evm::log(EarningsWithdrawn {
+            creator,
+            amount,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    // tokens an nft creator can withdraw
+    pub fn earnings_of(&self, creator: Address) -> U256 {
+        self.earnings.get(creator)
+    }
+
+    // casts a vote using a signed permit for the bid instead of a prior approve
+    pub fn cast_vote_with_permit(
+        &mut self,
+        gallery_id: U256,
+        nft_id: U256,
+        bid: U256,
+        deadline: U256,
+        v: u8,
+        r: B256,
+        s: B256
+    ) -> Result<(), CastError> {
+        self.permit_tf(bid, deadline, v, r, s);
+        self.cast_vote(gallery_id, nft_id, bid)
+    }
+
+    // increases a casted vote using a signed permit for the difference between the new and old bid
+    pub fn increase_cast_with_permit(
+        &mut self,
+        gallery_id: U256,
+        nft_id: U256,
+        vote_id: U256,
+        bid: U256,
+        deadline: U256,
+        v: u8,
+        r: B256,
+        s: B256
+    ) -> Result<(), CastError> {
+        let old_bid = self.get_staking_data(gallery_id, nft_id, vote_id, msg::sender())?;
+        if old_bid < bid {
+            self.permit_tf(bid - old_bid, deadline, v, r, s);
+        }
+        self.increase_cast(gallery_id, nft_id, vote_id, bid)
+    }
+
+    // safe function to set the admin and the needed contract address
+    pub fn set_control(
+        &mut self,
+        stake: Address, // unsafe stake contract address
+        erc20: Address, // token address
+        gallery: Address, // gallery address
+        libary: Address //nft_ libary contract address
+    ) -> Result<(), CastError> {
+        self.check_admin()?; // makes sure only the admin can call this function
+        self.stake.set(stake);
+        self.gallery_c.set(gallery);
+        self.erc20.set(erc20);
+        self.nft_libary.set(libary);
+        Ok(())
+    }
+}
+
+impl Cast {
+    // casts the vote of `user`; `prepaid` is set when the bid was already sent to this contract
+    pub fn cast(
+        &mut self,
+        user: Address,
+        gallery_id: U256,
+        nft_id: U256,
+        bid: U256,
+        prepaid: bool
+    ) -> Result<(), CastError> {
+        self.check_cancelled(gallery_id)?; // no votes on a cancelled gallery
+
         // getting gallary info;
         let (start, end, minimum_bid) = match self.get_gal_info(gallery_id) {
             Ok((start, end, minimum_bid)) => (start, end, minimum_bid),
@@ -90,8 +260,8 @@ impl Cast {
 
         if
             minimum_bid > bid || // checks if the bid is enough
-            !self.c_tik(gallery_id, msg::sender()) || // makes sure the user has a ticket
-            self.has_voted(gallery_id) // makes sure that the user has not voted
+            !self.c_tik(gallery_id, user) || // makes sure the user has a ticket
+            self.has_voted(gallery_id, user) // makes sure that the user has not voted
         {
             return Err(
                 CastError::InvalidState(InvalidState {
@@ -114,19 +284,23 @@ impl Cast {
             );
         }
 
-        self.fund_tf(nft_creator, bid)?; // attempts to transfer the funds to the creator
-        self.stake(gallery_id, nft_id, bid)?; // pass data to the unsafe contract
+        self.pay(nft_creator, bid, prepaid)?; // attempts to take the funds for the creator
+        self.stake(user, gallery_id, nft_id, bid)?; // pass data to the unsafe contract
         Ok(())
     }
 
-    // increse the value of the casted vote
-    pub fn increase_cast(
+    // increases the casted vote of `user`; `prepaid` is set when the difference was already sent to this contract
+    pub fn increase(
         &mut self,
+        user: Address,
         gallery_id: U256, // gallery identity
         nft_id: U256, //nft identity
         vote_id: U256, // cast id
-        bid: U256 // new bid value
+        bid: U256, // new bid value
+        prepaid: bool
     ) -> Result<(), CastError> {
+        self.check_cancelled(gallery_id)?; // no votes on a cancelled gallery
+
         // get the gallery info
         let (start, end, _minimum_bid) = match self.get_gal_info(gallery_id) {
             Ok((start, end, minimum_bid)) => (start, end, minimum_bid),
@@ -143,7 +317,7 @@ impl Cast {
         let nft_creator = self.get_creator(gallery_id, nft_id)?; //gets the creator of the nft
 
         // get data of the vote made
-        let old_bid = self.get_staking_data(gallery_id, nft_id, vote_id)?; // gets the old bid value
+        let old_bid = self.get_staking_data(gallery_id, nft_id, vote_id, user)?; // gets the old bid value
 
         // makes sure that the new bid is higher than the old bid
         if old_bid >= bid {
@@ -156,32 +330,15 @@ impl Cast {
 
         let balance_bid = bid - old_bid;
 
-        self.fund_tf(nft_creator, balance_bid)?; // attempts to transfer the balance to the creator
-        self.update_bid(gallery_id, nft_id, bid, vote_id)?; //update the bid value
-
-        Ok(())
-    }
+        self.pay(nft_creator, balance_bid, prepaid)?; // attempts to take the balance for the creator
+        self.update_bid(user, gallery_id, nft_id, bid, vote_id)?; //update the bid value
 
-    // safe function to set the admin and the needed contract address
-    pub fn set_control(
-        &mut self,
-        stake: Address, // unsafe stake contract address
-        erc20: Address, // token address
-        gallery: Address, // gallery address
-        libary: Address //nft_ libary contract address
-    ) -> Result<(), CastError> {
-        self.check_admin().map_err(|e| { e })?; // makes sure only the admin can call this function
-        self.stake.set(stake);
-        self.gallery_c.set(gallery);
-        self.erc20.set(erc20);
-        self.nft_libary.set(libary);
         Ok(())
     }
-}
 
-impl Cast {
     // info returns (start, end, minimum_bid)
     // get the gallery infromation
+    #[allow(clippy::result_unit_err)]
     pub fn get_gal_info(&self, gallery_index: U256) -> Result<(u64, u64, U256), ()> {
         let address = self.gallery_c.get();
         let gallery_contract = ISubject::new(address);
@@ -198,6 +355,21 @@ impl Cast {
         gallery_contract.get_user_status(config, gallery_index, user).expect("drat")
     }
 
+    // function to make sure the gallery has not been cancelled
+    pub fn check_cancelled(&self, gallery_id: U256) -> Result<(), CastError> {
+        let address = self.gallery_c.get();
+        let gallery_contract = ISubject::new(address);
+        let config = Call::new();
+        if gallery_contract.is_cancelled(config, gallery_id).expect("drat") {
+            return Err(
+                CastError::InvalidGallery(InvalidGallery {
+                    point: 203,
+                })
+            );
+        }
+        Ok(())
+    }
+
     // function to make sure we are still in the voting period
     pub fn check_time(&self, start: u64, end: u64) -> Result<(), CastError> {
         let current_time: u64 = block::timestamp() as u64;
@@ -212,7 +384,13 @@ impl Cast {
     }
 
     // function to cast the vote
-    pub fn stake(&mut self, gallery_id: U256, nft_id: U256, bid: U256) -> Result<(), CastError> {
+    pub fn stake(
+        &mut self,
+        user: Address,
+        gallery_id: U256,
+        nft_id: U256,
+        bid: U256
+    ) -> Result<(), CastError> {
         // Create a new instance of the stake interface
         let meta_date_contract = IStake::new(*self.stake);
 
@@ -220,7 +398,7 @@ impl Cast {
         let config = Call::new_in(self);
 
         // Attempt the vote
-        meta_date_contract.stake(config, msg::sender(), gallery_id, nft_id, bid).map_err(|_e| {
+        meta_date_contract.stake(config, user, gallery_id, nft_id, bid).map_err(|_e| {
             CastError::InvalidParameter(InvalidParameter {
                 point: 11,
             })
@@ -230,6 +408,7 @@ impl Cast {
     // function to update the bid of the user
     pub fn update_bid(
         &mut self,
+        user: Address,
         gallery_id: U256,
         nft_id: U256,
         bid: U256,
@@ -243,7 +422,7 @@ impl Cast {
 
         // Attempt the update
         meta_date_contract
-            .update_bid(config, msg::sender(), gallery_id, nft_id, vote_id, bid)
+            .update_bid(config, user, gallery_id, nft_id, vote_id, bid)
             .map_err(|_e| {
                 CastError::InvalidParameter(InvalidParameter {
                     point: 201,
@@ -252,15 +431,27 @@ impl Cast {
     }
 
     // check if the user has voted
-    pub fn has_voted(&self, gallery_id: U256) -> bool {
+    pub fn has_voted(&self, gallery_id: U256, user: Address) -> bool {
         let address = self.stake.get();
         let gallery_contract = IStake::new(address);
         let config = Call::new();
-        gallery_contract.has_voted(config, gallery_id, msg::sender()).expect("drat")
+        gallery_contract.has_voted(config, gallery_id, user).expect("drat")
     }
 
-    // This function handles the transfer of funds
-    pub fn fund_tf(&mut self, nft_creator: Address, price: U256) -> Result<bool, CastError> {
+    // pays the creator through this contract, which is a platform on the token, so votes can be
+    // paid with locked tokens; prepaid funds are already held here, and the creator withdraws them later
+    pub fn pay(&mut self, nft_creator: Address, amount: U256, prepaid: bool) -> Result<(), CastError> {
+        if !prepaid {
+            self.fund_tf(amount)?;
+        }
+        let mut earned = self.earnings.setter(nft_creator);
+        let old_earnings = earned.get();
+        earned.set(old_earnings + amount);
+        Ok(())
+    }
+
+    // This function takes the vote of the caller into this contract
+    pub fn fund_tf(&mut self, price: U256) -> Result<bool, CastError> {
         // Create a new instance of the ERC-20 interface
         let meta_date_contract = IErc20::new(*self.erc20);
 
@@ -268,13 +459,21 @@ impl Cast {
         let config = Call::new_in(self);
 
         // Attempt the transfer
-        meta_date_contract.transfer_from(config, msg::sender(), nft_creator, price).map_err(|_e| {
+        meta_date_contract.transfer_from(config, msg::sender(), contract::address(), price).map_err(|_e| {
             CastError::InvalidParameter(InvalidParameter {
                 point: 201,
             })
         })
     }
 
+    // submits the voter's permit for a bid; a failure is left to transfer_from (see the token's `permit`)
+    pub fn permit_tf(&mut self, value: U256, deadline: U256, v: u8, r: B256, s: B256) {
+        let args = (msg::sender(), contract::address(), value, deadline, U256::from(v), r, s)
+            .abi_encode_params();
+        let erc20 = *self.erc20;
+        let _ = call(Call::new_in(self), erc20, &[PERMIT.as_slice(), &args].concat());
+    }
+
     // control the admin state
     pub fn check_admin(&mut self) -> Result<bool, CastError> {
         let default_x: Address = Address::from([0x00; 20]);
@@ -287,10 +486,8 @@ impl Cast {
             );
         } else if self.admin.get() == default_x {
             self.admin.set(msg::sender());
-            return Ok(true);
-        } else {
-            return Ok(true);
         }
+        Ok(true)
     }
 
     // get the creator of the nft
@@ -312,7 +509,8 @@ impl Cast {
         &self,
         gallery_id: U256, // gallery identity
         nft_id: U256, // nft identity
-        vote_id: U256 // casted vote identity
+        vote_id: U256, // casted vote identity
+        user: Address // owner of the casted vote
     ) -> Result<U256, CastError> {
         let address = self.stake.get();
         let stake_contract = IStake::new(address);
@@ -320,10 +518,13 @@ impl Cast {
         let data = stake_contract.get_cast(config, gallery_id, nft_id, vote_id).expect("drat");
 
         // makes sure that only the ownder of the cast can update it
-        if data.2 != msg::sender() {
+        if data.2 != user {
             return Err(CastError::InvalidParameter(InvalidParameter { point: 81 }));
         }
         // returns the old bid
         Ok(data.0)
     }
 }
+
+#[cfg(test)]
+mod tests;
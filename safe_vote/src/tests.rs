@@ -0,0 +1,211 @@
+//! Unit tests run the contract against an in-memory stand-in of the Stylus host:
+//! storage lives in a per-thread map, the caller, block and contract address
+//! are the fixed values below, and the token is answered from the per-thread
+//! `World` instead of being called.
+
+use super::*;
+
+// the account every call is made from
+const SENDER: Address = Address::new([0x11; 20]);
+const SAFE_VOTE: Address = Address::new([0x22; 20]);
+const CREATOR: Address = Address::new([0xc1; 20]);
+const NOW: u64 = 1_700_000_000;
+const BLOCK: u64 = 1_000;
+
+mod host {
+    use super::{ Address, BLOCK, NOW, SAFE_VOTE, SENDER };
+    use alloy_sol_types::SolValue;
+    use sha3::{ Digest, Keccak256 };
+    use std::{ cell::RefCell, collections::HashMap };
+
+    // what the token has been asked to do
+    #[derive(Default)]
+    pub struct World {
+        pub calls: Vec<Vec<u8>>, // calldata of every state changing call
+        returned: Vec<u8>,
+    }
+
+    thread_local! {
+        static STORAGE: RefCell<HashMap<[u8; 32], [u8; 32]>> = RefCell::new(HashMap::new());
+        static WORLD: RefCell<World> = RefCell::new(World::default());
+    }
+
+    pub fn world<R>(f: impl FnOnce(&mut World) -> R) -> R {
+        WORLD.with(|w| f(&mut w.borrow_mut()))
+    }
+
+    pub fn selector(signature: &str) -> [u8; 4] {
+        Keccak256::digest(signature.as_bytes())[..4].try_into().unwrap()
+    }
+
+    // the token answers every call with true
+    unsafe fn call(calldata: *const u8, calldata_len: usize, records: bool) -> usize {
+        let calldata = std::slice::from_raw_parts(calldata, calldata_len);
+        world(|world| {
+            if records {
+                world.calls.push(calldata.to_vec());
+            }
+            world.returned = true.abi_encode();
+            world.returned.len()
+        })
+    }
+
+    unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+    }
+
+    unsafe fn address(dest: *mut u8, address: Address) {
+        write(dest, address.as_slice());
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = STORAGE.with(|s| s.borrow().get(&key).copied().unwrap_or_default());
+        write(dest, &value);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = *(value as *const [u8; 32]);
+        STORAGE.with(|s| s.borrow_mut().insert(key, value));
+    }
+
+    #[no_mangle]
+    extern "C" fn storage_flush_cache(_clear: bool) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+        let input = std::slice::from_raw_parts(bytes, len);
+        write(output, &Keccak256::digest(input));
+    }
+
+    #[no_mangle]
+    extern "C" fn emit_log(_data: *const u8, _len: usize, _topics: usize) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        address(sender, SENDER);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_value(value: *mut u8) {
+        write(value, &[0; 32]);
+    }
+
+    #[no_mangle]
+    extern "C" fn msg_reentrant() -> bool {
+        false
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn contract_address(dest: *mut u8) {
+        address(dest, SAFE_VOTE);
+    }
+
+    #[no_mangle]
+    extern "C" fn block_timestamp() -> u64 {
+        NOW
+    }
+
+    #[no_mangle]
+    extern "C" fn block_number() -> u64 {
+        BLOCK
+    }
+
+    #[no_mangle]
+    extern "C" fn chainid() -> u64 {
+        42161
+    }
+
+    #[no_mangle]
+    extern "C" fn account_code_size(_address: *const u8) -> usize {
+        0
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn call_contract(
+        _contract: *const u8,
+        calldata: *const u8,
+        calldata_len: usize,
+        _value: *const u8,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = call(calldata, calldata_len, true);
+        0
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn delegate_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn static_call_contract(
+        _contract: *const u8,
+        calldata: *const u8,
+        calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = call(calldata, calldata_len, false);
+        0
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn read_return_data(dest: *mut u8, offset: usize, size: usize) -> usize {
+        world(|world| {
+            let returned = world.returned.get(offset..).unwrap_or_default();
+            let size = size.min(returned.len());
+            write(dest, &returned[..size]);
+            size
+        })
+    }
+
+    #[no_mangle]
+    extern "C" fn return_data_size() -> usize {
+        world(|world| world.returned.len())
+    }
+}
+
+use alloy_sol_types::SolValue;
+use host::{ selector, world };
+
+// a fresh safe vote; storage and the world are empty on every test thread
+fn safe_vote() -> Cast {
+    unsafe { <Cast as stylus_sdk::storage::StorageType>::new(U256::ZERO, 0) }
+}
+
+fn ok<T>(result: Result<T, CastError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("call reverted: 0x{}", alloy_primitives::hex::encode(Vec::<u8>::from(e))),
+    }
+}
+
+#[test]
+fn votes_are_paid_into_this_contract_for_the_creator() {
+    let mut safe_vote = safe_vote();
+    ok(safe_vote.pay(CREATOR, U256::from(100), false));
+
+    // the voter pays this platform contract, never the creator, so locked tokens can pay for votes
+    let calls = world(|world| world.calls.clone());
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0][..4], selector("transferFrom(address,address,uint256)"));
+    assert_eq!(calls[0][4..], (SENDER, SAFE_VOTE, U256::from(100)).abi_encode_params());
+    assert_eq!(safe_vote.earnings_of(CREATOR), U256::from(100));
+
+    // tokens sent with transferAndCall are already here
+    ok(safe_vote.pay(CREATOR, U256::from(50), true));
+    assert_eq!(world(|world| world.calls.len()), 1);
+    assert_eq!(safe_vote.earnings_of(CREATOR), U256::from(150));
+}
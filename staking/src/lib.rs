@@ -1,7 +1,7 @@
 #![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
 extern crate alloc;
 
-use alloy_primitives::{ Address, U256, U8, U32, U64 };
+use alloy_primitives::{ Address, U256, U8, U32 };
 use stylus_sdk::{ prelude::*, msg, evm, block };
 use alloy_sol_types::sol;
 // so the nft will be minted with the gallery it was in
@@ -42,6 +42,7 @@ sol_storage! {
         // index casted votes
         mapping(uint256 => Cast ) casted;  // this is the identy of each of the user that has casted a vot
         // therefore each vote has an idnetity 
+        uint256 total_stake; // this is the sum of the value of all the votes on the nft
 
     }
 
@@ -114,6 +115,8 @@ impl Stake {
         // Collect data for the leaderboard update and release the mutable borrow
         // increases the total votes
         nft.total_votes.set(available_index); // sets new votes
+        let total_stake = nft.total_stake.get();
+        nft.total_stake.set(total_stake + bid); // adds the vote value to the nft stake
 
         let gallery_total_vote = gallery.total_votes.get(); // increase the totalvotes in the gallery
         gallery.total_votes.set(gallery_total_vote + U256::from(1));
@@ -170,6 +173,9 @@ impl Stake {
         // setting the new bid
         cast_vote.bid.set(bid);
         cast_vote.updated.set(U32::from(block::timestamp())); //set time of operation
+        let total_stake = nft.total_stake.get();
+        // saturating: a vote cast before total_stake existed is not in the sum until it is synced
+        nft.total_stake.set(total_stake.saturating_sub(old_bid) + bid); // replaces the old vote value in the nft stake
         self.update_le_nft(gallery_id, nft_id, vote_id, bid); // attempt to update the leaderboard
 
         // emit the operation
@@ -191,6 +197,28 @@ impl Stake {
         nft.total_votes.get()
     }
 
+    //get the total value staked on a particular nft
+    pub fn get_total_stake(&self, gallery_id: U256, nft_id: U256) -> U256 {
+        let gallery = self.room.getter(gallery_id);
+        let nft = gallery.nft.getter(nft_id);
+        nft.total_stake.get()
+    }
+
+    // recounts the total stake of an nft from all of its votes
+    // votes cast before total_stake was added to the contract are only counted once this is called
+    pub fn sync_total_stake(&mut self, gallery_id: U256, nft_id: U256) -> U256 {
+        let mut gallery = self.room.setter(gallery_id);
+        let mut nft = gallery.nft.setter(nft_id);
+        let mut total_stake = U256::ZERO;
+        let mut vote_id = U256::from(1);
+        while vote_id <= nft.total_votes.get() {
+            total_stake += nft.casted.getter(vote_id).bid.get();
+            vote_id += U256::from(1);
+        }
+        nft.total_stake.set(total_stake);
+        total_stake
+    }
+
     // get the information of a cast, using the vote id
     pub fn get_cast(&self, gallery_id: U256, nft_id: U256, vote_id: U256) -> (U256, u32, Address) {
         let gallery = self.room.getter(gallery_id);
@@ -215,7 +243,7 @@ impl Stake {
         // Fetch current leaderboard
         let leaderboard: Vec<(U256, U256, Address, u32)> = (start..end)
             .map(|i| {
-                let vote_id = nft.leaderboard.getter(U8::from(i as u8)).get(); // getting the vote id
+                let vote_id = nft.leaderboard.getter(U8::from(i)).get(); // getting the vote id
                 let bid = nft.casted.getter(vote_id).bid.get(); // the bid amount
                 let voter = nft.casted.getter(vote_id).voter.get(); //the voter
                 let updated = nft.casted.getter(vote_id).updated.get().to::<u32>(); //last time the bid was updated
@@ -228,7 +256,7 @@ impl Stake {
 
     // this is to set the admin and control the contract to make sure that only the admin can set a safe contract
     pub fn set_control(&mut self, stake: Address) -> Result<(), StakeError> {
-        self.check_admin().map_err(|e| { e })?; //makes sure only the admin can call this function
+        self.check_admin()?; //makes sure only the admin can call this function
         self.stake_control.set(stake);
         Ok(())
     }
@@ -267,11 +295,11 @@ impl Stake {
             //     )
             // }
             if board_voter == user {
-                return Ok(i as u8);
+                return Ok(i);
             }
         }
 
-        return Ok(LEADERBOARD_SIZE + 3);
+        Ok(LEADERBOARD_SIZE + 3)
     }
 }
 
@@ -285,7 +313,7 @@ impl Stake {
         // Fetch current leaderboard
         let mut leaderboard: Vec<(U256, U256)> = (0..LEADERBOARD_SIZE)
             .map(|i| {
-                let vote_id = nft.leaderboard.getter(U8::from(i as u8)).get();
+                let vote_id = nft.leaderboard.getter(U8::from(i)).get();
                 let bid = nft.casted.getter(vote_id).bid.get();
                 (vote_id, bid)
             })
@@ -321,9 +349,7 @@ impl Stake {
             );
         } else if self.admin.get() == default_x {
             self.admin.set(msg::sender());
-            return Ok(true);
-        } else {
-            return Ok(true);
         }
+        Ok(true)
     }
 }
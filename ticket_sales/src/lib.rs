@@ -1,11 +1,19 @@
 #![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
 extern crate alloc;
 
-use alloy_primitives::{ Address, U256 };
-use stylus_sdk::{ prelude::*, msg, evm, block };
-use alloy_sol_types::sol;
+use alloy_primitives::{ Address, B256, FixedBytes, U256 };
+use stylus_sdk::{ prelude::*, abi::Bytes, msg, evm, block, contract };
+use alloy_sol_types::{ sol, SolValue };
 
-use stylus_sdk::call::Call;
+use stylus_sdk::call::{ call, Call };
+
+// permit(address,address,uint256,uint256,uint8,bytes32,bytes32), called by hand (see the token's `permit`)
+const PERMIT: [u8; 4] = [0xd5, 0x05, 0xac, 0xcf];
+
+// selector returned to the token when a ticket is paid with transferAndCall
+const ON_TRANSFER_RECEIVED: [u8; 4] = [0x88, 0xa7, 0xca, 0x5c];
+// selector returned to the erc1155 when a redeemed SFT is exchanged for a ticket
+const ON_REDEEM: [u8; 4] = [0x99, 0xe4, 0x21, 0x2d];
 
 sol_storage! {
     #[entrypoint]
@@ -13,12 +21,23 @@ sol_storage! {
         address gallery_c;
         address erc20;
         address admin;
+        // ticket payments released from escrow that the gallery creators can withdraw
+        mapping(address => uint256) earnings;
+        // erc1155 contract whose SFTs can be redeemed for a free ticket
+        address erc1155;
+        // ticket payments held until voting ends, so they can be refunded if the gallery is cancelled
+        mapping(uint256 => uint256) escrow; // gallery index -> payments held
+        mapping(uint256 => mapping(address => uint256)) paid; // gallery index -> buyer -> price paid
+        mapping(uint256 => mapping(address => uint256)) paid_locked; // gallery index -> buyer -> part of the price paid with locked tokens
     }
 }
 
 sol_interface! {
     interface IErc20 {
         function transferFrom(address from, address to, uint256 value) external returns (bool);
+        function transfer(address to, uint256 value) external returns (bool);
+        function platformLockedOf(address platform, address account) external view returns (uint256);
+        function refundLocked(address to, uint256 value, uint256 locked) external returns (uint256);
     }
 
     interface ISubject {
@@ -26,6 +45,7 @@ sol_interface! {
         function buyTicket(uint256 gallery_index, address user) external;
         function getLastIndex() external view returns (uint256);
         function getUserStatus(uint256 gallery_index, address user) external view returns (bool);
+        function isCancelled(uint256 gallery_index) external view returns (bool);
     }    
 }
 
@@ -37,6 +57,16 @@ sol! {
 
     event SoldTicket(address indexed seller, uint256 indexed gallery_index, uint256 price, uint64 time);
 
+    event EarningsWithdrawn(address indexed seller, uint256 amount, uint64 time);
+
+    event EscrowReleased(address indexed seller, uint256 indexed gallery_index, uint256 amount, uint64 time);
+
+    // event to show that a ticket of a cancelled gallery has been refunded
+    event TicketRefunded(address indexed buyer, uint256 indexed gallery_index, uint256 amount, uint64 time);
+
+    // event to show that a ticket was given for a redeemed SFT
+    event RedeemedTicket(address indexed buyer, uint256 indexed gallery_index, uint256 indexed nft_id, uint64 time);
+
     // my error
     // error to show invalid parameter
     error InvalidParameter(uint8 point);
@@ -45,6 +75,7 @@ sol! {
 
     error ExistingTicket(uint256 gallery_index);
     error InSufficientAllowance(uint256 gallery_index);
+    error WrongPayment(uint256 gallery_index, uint256 price, uint256 value);
 }
 
 #[derive(SolidityError)]
@@ -53,87 +84,343 @@ pub enum TicketError {
     ExistingTicket(ExistingTicket),
     InSufficientAllowance(InSufficientAllowance),
     NoData(NoData),
+    WrongPayment(WrongPayment),
 }
 
 #[public]
 impl Buy {
     pub fn buy_ticket(&mut self, gallery_index: U256) -> Result<(), TicketError> {
-        // check if the gallery index is valid
-        if !self.i_chk(gallery_index) {
+        let buyer = msg::sender();
+        let (creator, price) = self.ticket_chk(gallery_index, buyer)?;
+
+        if price != U256::from(0) {
+            // Pay for the ticket into escrow and propagate errors
+            let paid_in = self.locked_paid_in(buyer);
+            self
+                .fund_tf(price)
+                .map_err(|_| {
+                    TicketError::InSufficientAllowance(InSufficientAllowance { gallery_index })
+                })?;
+            let locked = self.locked_paid_in(buyer).saturating_sub(paid_in);
+            self.hold(gallery_index, buyer, price, locked);
+        }
+
+        self.issue_tik(gallery_index, buyer, creator, price);
+        Ok(())
+    }
+
+    // ERC-1363 hook: buys a ticket with tokens sent through the token's transferAndCall
+    // `data` holds the gallery index; the payment must be the exact ticket price
+    // the tokens are already held by this contract, so the price goes straight into escrow;
+    // the token can not be called back from here, so it appends the part paid with locked tokens to `data`
+    pub fn on_transfer_received(
+        &mut self,
+        _operator: Address,
+        from: Address,
+        value: U256,
+        data: Bytes
+    ) -> Result<FixedBytes<4>, TicketError> {
+        if msg::sender() != self.erc20.get() || data.len() < 32 {
             return Err(
                 TicketError::InvalidParameter(InvalidParameter {
-                    point: 1,
+                    point: 10,
                 })
             );
         }
 
-        // check if user has a ticket
-        if self.c_tik(gallery_index) {
-            return Err(TicketError::ExistingTicket(ExistingTicket { gallery_index }));
+        let gallery_index = U256::from_be_slice(&data[..32]);
+        let (creator, price) = self.ticket_chk(gallery_index, from)?;
+        if value != price {
+            return Err(TicketError::WrongPayment(WrongPayment { gallery_index, price, value }));
         }
 
-        // getting gallary info
-        let (creator, price) = match self.get_gal_info(gallery_index) {
-            Ok((creator, price)) => (creator, price),
-            Err(_) => {
-                return Err(TicketError::NoData(NoData {}));
-            }
+        let locked = if data.len() >= 64 {
+            U256::from_be_slice(&data[data.len() - 32..])
+        } else {
+            U256::from(0)
         };
+        self.hold(gallery_index, from, price, locked.min(price));
+        self.issue_tik(gallery_index, from, creator, price);
+        Ok(FixedBytes(ON_TRANSFER_RECEIVED))
+    }
 
-        if price != U256::from(0) {
-            // Pay for the ticket and propagate errors
-            self
-                .fund_tf(creator, price)
-                .map_err(|_| {
-                    TicketError::InSufficientAllowance(InSufficientAllowance { gallery_index })
-                })?;
+    // redemption hook: gives a free ticket for an SFT burned on the erc1155
+    // `data` holds the gallery index; the gallery must belong to the creator of the SFT
+    pub fn on_redeem(
+        &mut self,
+        holder: Address,
+        id: U256,
+        _amount: U256,
+        creator: Address,
+        data: Bytes
+    ) -> Result<FixedBytes<4>, TicketError> {
+        if msg::sender() != self.erc1155.get() || data.len() < 32 {
+            return Err(
+                TicketError::InvalidParameter(InvalidParameter {
+                    point: 13,
+                })
+            );
         }
-        // set data in the gallery
-        self.up_tik(gallery_index);
 
-        // this will send an event that the user has bought the ticket '
-        evm::log(BoughtTicket {
-            buyer: msg::sender(),
+        let gallery_index = U256::from_be_slice(&data[..32]);
+        let (gallery_creator, _price) = self.ticket_chk(gallery_index, holder)?;
+        if gallery_creator != creator {
+            return Err(
+                TicketError::InvalidParameter(InvalidParameter {
+                    point: 14,
+                })
+            );
+        }
+
+        self.issue_tik(gallery_index, holder, gallery_creator, U256::from(0));
+        evm::log(RedeemedTicket {
+            buyer: holder,
             gallery_index,
-            price,
+            nft_id: id,
             time: block::timestamp() as u64,
         });
+        Ok(FixedBytes(ON_REDEEM))
+    }
 
-        // this event to to show the user that a ticket has been sold
-        evm::log(SoldTicket {
+    // moves the ticket payments of a gallery out of escrow to its creator once voting has ended
+    pub fn release_escrow(&mut self, gallery_index: U256) -> Result<(), TicketError> {
+        let (creator, _price) = self.get_gal_info(gallery_index).map_err(|_| {
+            TicketError::NoData(NoData {})
+        })?;
+        if
+            self.is_cancelled(gallery_index) || // cancelled galleries are refunded instead
+            block::timestamp() <= self.voting_end(gallery_index) // voting has not ended yet
+        {
+            return Err(
+                TicketError::InvalidParameter(InvalidParameter {
+                    point: 16,
+                })
+            );
+        }
+
+        let amount = self.escrow.get(gallery_index);
+        if amount == U256::from(0) {
+            return Err(TicketError::NoData(NoData {}));
+        }
+        self.escrow.setter(gallery_index).set(U256::from(0));
+
+        let mut earned = self.earnings.setter(creator);
+        let old_earnings = earned.get();
+        earned.set(old_earnings + amount);
+
+        evm::log(EscrowReleased {
             seller: creator,
             gallery_index,
-            price,
+            amount,
             time: block::timestamp() as u64,
         });
+        Ok(())
+    }
+
+    // gives the buyer back the price of their ticket to a cancelled gallery
+    pub fn claim_refund(&mut self, gallery_index: U256) -> Result<(), TicketError> {
+        if !self.is_cancelled(gallery_index) {
+            return Err(
+                TicketError::InvalidParameter(InvalidParameter {
+                    point: 17,
+                })
+            );
+        }
 
+        let buyer = msg::sender();
+        let amount = self.paid.getter(gallery_index).get(buyer);
+        let held = self.escrow.get(gallery_index);
+        // the gallery can not be cancelled after voting ends, so the escrow still holds every payment
+        if amount == U256::from(0) || held < amount {
+            return Err(TicketError::NoData(NoData {}));
+        }
+
+        let locked = self.paid_locked.getter(gallery_index).get(buyer);
+        self.paid.setter(gallery_index).setter(buyer).set(U256::from(0));
+        self.paid_locked.setter(gallery_index).setter(buyer).set(U256::from(0));
+        self.escrow.setter(gallery_index).set(held - amount);
+        // a price paid with locked tokens goes back under the buyer's lock
+        let refunded = if locked == U256::from(0) {
+            self.fund_fw(buyer, amount)
+        } else {
+            self.refund_fw(buyer, amount, locked)
+        };
+        refunded.map_err(|_| {
+            TicketError::InvalidParameter(InvalidParameter {
+                point: 18,
+            })
+        })?;
+
+        evm::log(TicketRefunded {
+            buyer,
+            gallery_index,
+            amount,
+            time: block::timestamp() as u64,
+        });
         Ok(())
     }
 
+    // ticket payments of a gallery still held in escrow
+    pub fn escrow_of(&self, gallery_index: U256) -> U256 {
+        self.escrow.get(gallery_index)
+    }
+
+    // price `buyer` paid for their ticket to the gallery, until it is refunded
+    pub fn paid_for(&self, gallery_index: U256, buyer: Address) -> U256 {
+        self.paid.getter(gallery_index).get(buyer)
+    }
+
+    // lets a gallery creator withdraw the ticket payments released from escrow
+    pub fn withdraw_earnings(&mut self) -> Result<(), TicketError> {
+        let seller = msg::sender();
+        let amount = self.earnings.get(seller);
+        if amount == U256::from(0) {
+            return Err(TicketError::NoData(NoData {}));
+        }
+
+        self.earnings.setter(seller).set(U256::from(0));
+        self.fund_fw(seller, amount).map_err(|_| {
+            TicketError::InvalidParameter(InvalidParameter {
+                point: 12,
+            })
+        })?;
+
+        evm::log(EarningsWithdrawn {
+            seller,
+            amount,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    // tokens a gallery creator can withdraw
+    pub fn earnings_of(&self, seller: Address) -> U256 {
+        self.earnings.get(seller)
+    }
+
+    // buys a ticket using a signed permit for the ticket price instead of a prior approve
+    pub fn buy_ticket_with_permit(
+        &mut self,
+        gallery_index: U256,
+        deadline: U256,
+        v: u8,
+        r: B256,
+        s: B256
+    ) -> Result<(), TicketError> {
+        let (_creator, price) = self.ticket_chk(gallery_index, msg::sender())?;
+
+        if price != U256::from(0) {
+            self.permit_tf(price, deadline, v, r, s);
+        }
+
+        self.buy_ticket(gallery_index)
+    }
+
     pub fn set_erc20_gallery(
         &mut self,
         er20_address: Address,
         gallery_address: Address
     ) -> Result<(), TicketError> {
-        self.check_admin().map_err(|e| { e })?;
+        self.check_admin()?;
         self.erc20.set(er20_address);
         self.gallery_c.set(gallery_address);
         Ok(())
     }
+
+    pub fn set_erc1155(&mut self, erc1155_address: Address) -> Result<(), TicketError> {
+        self.check_admin()?;
+        self.erc1155.set(erc1155_address);
+        Ok(())
+    }
 }
 
 // helper functions
 
 impl Buy {
+    // checks that `buyer` can get a ticket to the gallery
+    // returns (creator_address and price of the ticket)
+    pub fn ticket_chk(
+        &self,
+        gallery_index: U256,
+        buyer: Address
+    ) -> Result<(Address, U256), TicketError> {
+        // check if the gallery index is valid
+        if !self.i_chk(gallery_index) {
+            return Err(
+                TicketError::InvalidParameter(InvalidParameter {
+                    point: 1,
+                })
+            );
+        }
+
+        // tickets to a cancelled gallery are no longer sold
+        if self.is_cancelled(gallery_index) {
+            return Err(
+                TicketError::InvalidParameter(InvalidParameter {
+                    point: 15,
+                })
+            );
+        }
+
+        // check if user has a ticket
+        if self.c_tik(gallery_index, buyer) {
+            return Err(TicketError::ExistingTicket(ExistingTicket { gallery_index }));
+        }
+
+        // getting gallary info
+        match self.get_gal_info(gallery_index) {
+            Ok((creator, price)) => Ok((creator, price)),
+            Err(_) => Err(TicketError::NoData(NoData {})),
+        }
+    }
+
+    // records the ticket payment of `buyer` in the escrow of the gallery,
+    // with the part of it the buyer paid from their locked grant
+    pub fn hold(&mut self, gallery_index: U256, buyer: Address, price: U256, locked: U256) {
+        let held = self.escrow.get(gallery_index);
+        self.escrow.setter(gallery_index).set(held + price);
+        self.paid.setter(gallery_index).setter(buyer).set(price);
+        self.paid_locked.setter(gallery_index).setter(buyer).set(locked);
+    }
+
+    // gives `buyer` the ticket and emits the sale
+    pub fn issue_tik(&mut self, gallery_index: U256, buyer: Address, creator: Address, price: U256) {
+        // set data in the gallery
+        self.up_tik(gallery_index, buyer);
+
+        // this will send an event that the user has bought the ticket '
+        evm::log(BoughtTicket {
+            buyer,
+            gallery_index,
+            price,
+            time: block::timestamp() as u64,
+        });
+
+        // this event to to show the user that a ticket has been sold
+        evm::log(SoldTicket {
+            seller: creator,
+            gallery_index,
+            price,
+            time: block::timestamp() as u64,
+        });
+    }
+
     // function to check if the user has a ticket
-    pub fn c_tik(&self, gallery_index: U256) -> bool {
-        let user = msg::sender();
+    pub fn c_tik(&self, gallery_index: U256, user: Address) -> bool {
         let address = self.gallery_c.get();
         let gallery_contract = ISubject::new(address);
         let config = Call::new();
         gallery_contract.get_user_status(config, gallery_index, user).expect("drat")
     }
 
+    // function to check if the gallery has been cancelled
+    pub fn is_cancelled(&self, gallery_index: U256) -> bool {
+        let address = self.gallery_c.get();
+        let gallery_contract = ISubject::new(address);
+        let config = Call::new();
+        gallery_contract.is_cancelled(config, gallery_index).expect("drat")
+    }
+
     // function to check if the index given is correct
     pub fn i_chk(&self, gallery_index: U256) -> bool {
         let address = self.gallery_c.get();
@@ -148,8 +435,8 @@ impl Buy {
         true
     }
 
-    // This function handles the transfer of funds
-    pub fn fund_tf(&mut self, gallery_creator: Address, price: U256) -> Result<bool, u8> {
+    // This function takes the ticket price from the buyer into escrow
+    pub fn fund_tf(&mut self, price: U256) -> Result<bool, u8> {
         // Create a new instance of the ERC-20 interface
         let meta_date_contract = IErc20::new(*self.erc20);
 
@@ -158,12 +445,45 @@ impl Buy {
 
         // Attempt the transfer
         meta_date_contract
-            .transfer_from(config, msg::sender(), gallery_creator, price)
-            .map_err(|_e| 0 as u8)
+            .transfer_from(config, msg::sender(), contract::address(), price)
+            .map_err(|_e| 0u8)
+    }
+
+    // this function sends tokens held by this contract to `to`
+    pub fn fund_fw(&mut self, to: Address, amount: U256) -> Result<bool, u8> {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract.transfer(config, to, amount).map_err(|_e| 0u8)
+    }
+
+    // this function refunds `amount` to `to`, putting up to `locked` of it back under their lock
+    pub fn refund_fw(&mut self, to: Address, amount: U256, locked: U256) -> Result<bool, u8> {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new_in(self);
+        meta_date_contract
+            .refund_locked(config, to, amount, locked)
+            .map(|_relocked| true)
+            .map_err(|_e| 0u8)
+    }
+
+    // locked tokens of `buyer` the token has recorded as paid into this contract and not refunded
+    pub fn locked_paid_in(&self, buyer: Address) -> U256 {
+        let meta_date_contract = IErc20::new(*self.erc20);
+        let config = Call::new();
+        meta_date_contract.platform_locked_of(config, contract::address(), buyer).expect("drat")
+    }
+
+    // submits the buyer's permit for the ticket price; a failure is left to transfer_from (see the token's `permit`)
+    pub fn permit_tf(&mut self, value: U256, deadline: U256, v: u8, r: B256, s: B256) {
+        let args = (msg::sender(), contract::address(), value, deadline, U256::from(v), r, s)
+            .abi_encode_params();
+        let erc20 = *self.erc20;
+        let _ = call(Call::new_in(self), erc20, &[PERMIT.as_slice(), &args].concat());
     }
 
     // this function gets the needed gallery info for the buying of ticket to be succesful
     // here we are returning the (creator_address and price of the ticket)
+    #[allow(clippy::result_unit_err)]
     pub fn get_gal_info(&self, gallery_index: U256) -> Result<(Address, U256), ()> {
         let address = self.gallery_c.get();
         let gallery_contract = ISubject::new(address);
@@ -172,9 +492,17 @@ impl Buy {
         Ok((data.0, data.5))
     }
 
+    // the time the voting of the gallery ends
+    pub fn voting_end(&self, gallery_index: U256) -> u64 {
+        let address = self.gallery_c.get();
+        let gallery_contract = ISubject::new(address);
+        let config = Call::new();
+        gallery_contract.get_gallery(config, gallery_index).expect("drat").6
+    }
+
     // this function will be incharge of updating the status of the buying of ticket
 
-    pub fn up_tik(&mut self, gallery_index: U256) {
+    pub fn up_tik(&mut self, gallery_index: U256, user: Address) {
         // update gallary information
         let meta_date_contract = ISubject::new(*self.gallery_c);
 
@@ -183,8 +511,8 @@ impl Buy {
 
         // Attempt the transfer
         let _ = meta_date_contract
-            .buy_ticket(config, gallery_index, msg::sender())
-            .map_err(|_e| 0 as u8);
+            .buy_ticket(config, gallery_index, user)
+            .map_err(|_e| 0u8);
     }
 
     pub fn check_admin(&mut self) -> Result<bool, TicketError> {
@@ -197,9 +525,10 @@ impl Buy {
             );
         } else if self.admin.get() == default_x {
             self.admin.set(msg::sender());
-            return Ok(true);
-        } else {
-            return Ok(true);
         }
+        Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,400 @@
+//! Unit tests run the contract against an in-memory stand-in of the Stylus host:
+//! storage lives in a per-thread map, the caller, block and contract address
+//! are the fixed values below, and the gallery and token contracts are answered
+//! from the per-thread `World` instead of being called.
+
+use super::*;
+
+// the account every call is made from; it becomes the admin on its first admin call
+const SENDER: Address = Address::new([0x11; 20]);
+const SALES: Address = Address::new([0x22; 20]);
+const CREATOR: Address = Address::new([0xc1; 20]);
+const NOW: u64 = 1_700_000_000;
+const BLOCK: u64 = 1_000;
+
+mod host {
+    use super::{ Address, BLOCK, CREATOR, NOW, SALES, SENDER, U256 };
+    use alloy_sol_types::SolValue;
+    use sha3::{ Digest, Keccak256 };
+    use std::{ cell::RefCell, collections::HashMap };
+
+    // what the gallery and token contracts answer
+    #[derive(Default)]
+    pub struct World {
+        pub price: U256,
+        pub voting_end: u64,
+        pub cancelled: bool,
+        pub locked_paid_in: U256, // platformLockedOf(this contract, buyer)
+        pub locked_share: U256, // part of every transferFrom the token takes from a locked grant
+        pub calls: Vec<Vec<u8>>, // calldata of every state changing call
+        pub token: Address, // the token contract
+        pub in_hook: bool, // the token is running a hook, so calls back into it revert as re-entrant
+        pub no_allowance: bool, // the token rejects every permit and transferFrom
+        returned: Vec<u8>,
+    }
+
+    thread_local! {
+        static STORAGE: RefCell<HashMap<[u8; 32], [u8; 32]>> = RefCell::new(HashMap::new());
+        static WORLD: RefCell<World> = RefCell::new(World::default());
+    }
+
+    pub fn world<R>(f: impl FnOnce(&mut World) -> R) -> R {
+        WORLD.with(|w| f(&mut w.borrow_mut()))
+    }
+
+    pub fn selector(signature: &str) -> [u8; 4] {
+        Keccak256::digest(signature.as_bytes())[..4].try_into().unwrap()
+    }
+
+    // the return data of a call to the gallery or the token
+    fn answer(world: &World, calldata: &[u8]) -> Vec<u8> {
+        let selector: [u8; 4] = calldata[..4].try_into().unwrap();
+        if selector == self::selector("getGallery(uint256)") {
+            let gallery = (
+                CREATOR,
+                String::new(),
+                String::new(),
+                0u32,
+                0u64,
+                world.price,
+                world.voting_end,
+                0u64,
+                U256::ZERO,
+            );
+            // stylus encodes a returned tuple as a single value
+            gallery.abi_encode()
+        } else if selector == self::selector("getLastIndex()") {
+            U256::from(10).abi_encode()
+        } else if selector == self::selector("getUserStatus(uint256,address)") {
+            false.abi_encode()
+        } else if selector == self::selector("isCancelled(uint256)") {
+            world.cancelled.abi_encode()
+        } else if selector == self::selector("platformLockedOf(address,address)") {
+            world.locked_paid_in.abi_encode()
+        } else if selector == self::selector("refundLocked(address,uint256,uint256)") {
+            U256::from_be_slice(&calldata[68..100]).abi_encode()
+        } else if selector == self::selector("buyTicket(uint256,address)") {
+            Vec::new()
+        } else {
+            // transfer and transferFrom
+            true.abi_encode()
+        }
+    }
+
+    // the return data length of a call, or none when it reverts
+    unsafe fn call(
+        contract: *const u8,
+        calldata: *const u8,
+        calldata_len: usize,
+        records: bool
+    ) -> Option<usize> {
+        let contract = Address::from_slice(std::slice::from_raw_parts(contract, 20));
+        let calldata = std::slice::from_raw_parts(calldata, calldata_len);
+        world(|world| {
+            if world.in_hook && contract == world.token {
+                world.returned = Vec::new();
+                return None;
+            }
+            if records {
+                world.calls.push(calldata.to_vec());
+                let spends = [
+                    selector("permit(address,address,uint256,uint256,uint8,bytes32,bytes32)"),
+                    selector("transferFrom(address,address,uint256)"),
+                ];
+                if world.no_allowance && spends.contains(&calldata[..4].try_into().unwrap()) {
+                    world.returned = Vec::new();
+                    return None;
+                }
+                if calldata[..4] == selector("transferFrom(address,address,uint256)") {
+                    world.locked_paid_in += world.locked_share;
+                }
+            }
+            world.returned = answer(world, calldata);
+            Some(world.returned.len())
+        })
+    }
+
+    unsafe fn status(return_data_len: *mut usize, returned: Option<usize>) -> u8 {
+        *return_data_len = returned.unwrap_or_default();
+        returned.is_none().into()
+    }
+
+    unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+    }
+
+    unsafe fn address(dest: *mut u8, address: Address) {
+        write(dest, address.as_slice());
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = STORAGE.with(|s| s.borrow().get(&key).copied().unwrap_or_default());
+        write(dest, &value);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = *(value as *const [u8; 32]);
+        STORAGE.with(|s| s.borrow_mut().insert(key, value));
+    }
+
+    #[no_mangle]
+    extern "C" fn storage_flush_cache(_clear: bool) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+        let input = std::slice::from_raw_parts(bytes, len);
+        write(output, &Keccak256::digest(input));
+    }
+
+    #[no_mangle]
+    extern "C" fn emit_log(_data: *const u8, _len: usize, _topics: usize) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        address(sender, SENDER);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_value(value: *mut u8) {
+        write(value, &[0; 32]);
+    }
+
+    #[no_mangle]
+    extern "C" fn msg_reentrant() -> bool {
+        false
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn contract_address(dest: *mut u8) {
+        address(dest, SALES);
+    }
+
+    #[no_mangle]
+    extern "C" fn block_timestamp() -> u64 {
+        NOW
+    }
+
+    #[no_mangle]
+    extern "C" fn block_number() -> u64 {
+        BLOCK
+    }
+
+    #[no_mangle]
+    extern "C" fn chainid() -> u64 {
+        42161
+    }
+
+    #[no_mangle]
+    extern "C" fn account_code_size(_address: *const u8) -> usize {
+        0
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn call_contract(
+        contract: *const u8,
+        calldata: *const u8,
+        calldata_len: usize,
+        _value: *const u8,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        status(return_data_len, call(contract, calldata, calldata_len, true))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn delegate_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn static_call_contract(
+        contract: *const u8,
+        calldata: *const u8,
+        calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        status(return_data_len, call(contract, calldata, calldata_len, false))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn read_return_data(dest: *mut u8, offset: usize, size: usize) -> usize {
+        world(|world| {
+            let returned = world.returned.get(offset..).unwrap_or_default();
+            let size = size.min(returned.len());
+            write(dest, &returned[..size]);
+            size
+        })
+    }
+
+    #[no_mangle]
+    extern "C" fn return_data_size() -> usize {
+        world(|world| world.returned.len())
+    }
+}
+
+use alloy_sol_types::SolValue;
+use host::{ selector, world };
+
+// a fresh ticket sale; storage and the world are empty on every test thread
+fn sales() -> Buy {
+    unsafe { <Buy as stylus_sdk::storage::StorageType>::new(U256::ZERO, 0) }
+}
+
+fn ok<T>(result: Result<T, TicketError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("call reverted: 0x{}", alloy_primitives::hex::encode(Vec::<u8>::from(e))),
+    }
+}
+
+fn reverted_at<T>(result: Result<T, TicketError>, point: u8) -> bool {
+    matches!(result, Err(TicketError::InvalidParameter(e)) if e.point == point)
+}
+
+// a sale where SENDER bought a ticket to gallery 1 for `price`, `locked` of it from a locked grant
+fn sold(price: u64, locked: u64) -> Buy {
+    let mut sales = sales();
+    world(|world| {
+        world.price = U256::from(price);
+        world.voting_end = NOW + 86_400;
+        world.locked_share = U256::from(locked);
+    });
+    ok(sales.buy_ticket(U256::from(1)));
+    world(|world| world.calls.clear());
+    sales
+}
+
+// the last state changing call, split into its selector and arguments
+fn last_call() -> ([u8; 4], Vec<u8>) {
+    world(|world| {
+        let calldata = world.calls.last().expect("no call was made");
+        (calldata[..4].try_into().unwrap(), calldata[4..].to_vec())
+    })
+}
+
+#[test]
+fn release_escrow_waits_for_the_end_of_voting() {
+    let mut sales = sold(100, 0);
+    assert_eq!(sales.escrow_of(U256::from(1)), U256::from(100));
+
+    world(|world| {
+        world.voting_end = NOW;
+    });
+    assert!(reverted_at(sales.release_escrow(U256::from(1)), 16));
+
+    world(|world| {
+        world.voting_end = NOW - 1;
+    });
+    ok(sales.release_escrow(U256::from(1)));
+    assert_eq!(sales.escrow_of(U256::from(1)), U256::ZERO);
+    assert_eq!(sales.earnings_of(CREATOR), U256::from(100));
+    assert!(matches!(sales.release_escrow(U256::from(1)), Err(TicketError::NoData(_))));
+}
+
+#[test]
+fn release_escrow_leaves_a_cancelled_gallery_to_the_refunds() {
+    let mut sales = sold(100, 0);
+    world(|world| {
+        world.voting_end = NOW - 1;
+        world.cancelled = true;
+    });
+    assert!(reverted_at(sales.release_escrow(U256::from(1)), 16));
+    assert_eq!(sales.escrow_of(U256::from(1)), U256::from(100));
+}
+
+#[test]
+fn claim_refund_pays_back_the_ticket_price() {
+    let mut sales = sold(100, 0);
+    assert!(reverted_at(sales.claim_refund(U256::from(1)), 17));
+
+    world(|world| {
+        world.cancelled = true;
+    });
+    ok(sales.claim_refund(U256::from(1)));
+    assert_eq!(sales.escrow_of(U256::from(1)), U256::ZERO);
+    assert_eq!(sales.paid_for(U256::from(1), SENDER), U256::ZERO);
+
+    let (called, args) = last_call();
+    assert_eq!(called, selector("transfer(address,uint256)"));
+    assert_eq!(args, (SENDER, U256::from(100)).abi_encode_params());
+
+    // a ticket is only refunded once
+    assert!(matches!(sales.claim_refund(U256::from(1)), Err(TicketError::NoData(_))));
+}
+
+#[test]
+fn claim_refund_relocks_the_part_paid_with_locked_tokens() {
+    let mut sales = sold(100, 60);
+    assert_eq!(sales.paid_locked.getter(U256::from(1)).get(SENDER), U256::from(60));
+
+    world(|world| {
+        world.cancelled = true;
+    });
+    ok(sales.claim_refund(U256::from(1)));
+
+    let (called, args) = last_call();
+    assert_eq!(called, selector("refundLocked(address,uint256,uint256)"));
+    assert_eq!(args, (SENDER, U256::from(100), U256::from(60)).abi_encode_params());
+    assert_eq!(sales.paid_locked.getter(U256::from(1)).get(SENDER), U256::ZERO);
+}
+
+#[test]
+fn a_ticket_bought_through_transfer_and_call_never_calls_back_into_the_token() {
+    // the caller is fixed, so SENDER stands for both the token calling the hook and the buyer
+    let mut sales = sales();
+    sales.erc20.set(SENDER);
+    world(|world| {
+        world.price = U256::from(100);
+        world.voting_end = NOW + 86_400;
+        world.token = SENDER;
+        world.in_hook = true;
+    });
+
+    // the gallery index, followed by the locked part the token appends for a platform
+    let data = (U256::from(1), U256::from(60)).abi_encode_params();
+    let hook = sales.on_transfer_received(SENDER, SENDER, U256::from(100), data.into());
+    assert_eq!(ok(hook), FixedBytes(ON_TRANSFER_RECEIVED));
+    assert_eq!(sales.escrow_of(U256::from(1)), U256::from(100));
+    assert_eq!(sales.paid_locked.getter(U256::from(1)).get(SENDER), U256::from(60));
+
+    world(|world| {
+        world.in_hook = false;
+        world.cancelled = true;
+    });
+    ok(sales.claim_refund(U256::from(1)));
+    let (called, args) = last_call();
+    assert_eq!(called, selector("refundLocked(address,uint256,uint256)"));
+    assert_eq!(args, (SENDER, U256::from(100), U256::from(60)).abi_encode_params());
+}
+
+#[test]
+fn an_invalid_permit_without_an_allowance_still_fails_the_purchase() {
+    let mut sales = sales();
+    world(|world| {
+        world.price = U256::from(100);
+        world.voting_end = NOW + 86_400;
+        world.no_allowance = true;
+    });
+
+    // the rejected permit is ignored, and the transferFrom it could not approve fails the purchase
+    let bought = sales.buy_ticket_with_permit(U256::from(1), U256::from(NOW), 27, B256::ZERO, B256::ZERO);
+    assert!(matches!(bought, Err(TicketError::InSufficientAllowance(_))));
+    let called = world(|world| world.calls.iter().map(|c| c[..4].to_vec()).collect::<Vec<_>>());
+    assert_eq!(called, [
+        selector("permit(address,address,uint256,uint256,uint8,bytes32,bytes32)").to_vec(),
+        selector("transferFrom(address,address,uint256)").to_vec(),
+    ]);
+    assert_eq!(sales.escrow_of(U256::from(1)), U256::ZERO);
+}
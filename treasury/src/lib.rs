@@ -0,0 +1,197 @@
+#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
+extern crate alloc;
+
+use alloy_primitives::{ Address, U256 };
+use stylus_sdk::{ prelude::*, msg, evm, block, call::{ call, Call } };
+use alloy_sol_types::sol;
+
+const BPS: u64 = 10_000;
+
+sol_storage! {
+    // ledger of the token sale proceeds; the wei is held by this contract until withdrawn
+    #[entrypoint]
+    pub struct Treasury {
+        address admin;
+
+        uint256 treasury; // wei owed to beneficiaries and not yet withdrawn
+        mapping(address => uint256) proceeds; // wei owed to each beneficiary
+        address[] beneficiaries; // deposits are refused while this is empty
+        uint256[] beneficiary_bps; // share of each beneficiary; sums to BPS
+    }
+}
+
+//  ======   Errors and Events  =========  //
+
+sol! {
+    event SetBeneficiaries(address[] beneficiaries, uint256[] shares_bps, uint64 time);
+    event ProceedsAccrued(address indexed beneficiary, uint256 amount, uint256 balance, uint64 time);
+    event ProceedsWithdrawn(address indexed to, uint256 amount, uint256 balance, uint64 time);
+
+    // error to show invalid parameter
+    error InvalidParameter(uint8 point);
+
+    error InsufficientProceeds(address beneficiary, uint256 have, uint256 want);
+}
+
+#[derive(SolidityError)]
+pub enum TreasuryError {
+    InvalidParameter(InvalidParameter),
+    InsufficientProceeds(InsufficientProceeds),
+}
+
+#[public]
+impl Treasury {
+    // records the sent wei as proceeds, split between the beneficiaries;
+    // the token sends what it does not keep in its reserve here on every sale.
+    // Refused until the beneficiaries are set, which also fixes the admin, so no proceed is
+    // ever credited to an account nobody controls
+    #[payable]
+    pub fn deposit(&mut self) -> Result<(), TreasuryError> {
+        if self.beneficiaries.is_empty() {
+            return Err(
+                TreasuryError::InvalidParameter(InvalidParameter {
+                    point: 3,
+                })
+            );
+        }
+        self.accrue_proceeds(msg::value());
+        Ok(())
+    }
+
+    // sets who receives the proceeds and their share in basis points (the shares must sum to 10_000);
+    // an empty list stops deposits
+    pub fn set_beneficiaries(
+        &mut self,
+        beneficiaries: Vec<Address>,
+        shares_bps: Vec<U256>
+    ) -> Result<(), TreasuryError> {
+        self.check_admin()?;
+        let total = shares_bps.iter().fold(U256::ZERO, |total, share| total.saturating_add(*share));
+        if
+            beneficiaries.len() != shares_bps.len() ||
+            (!beneficiaries.is_empty() && total != U256::from(BPS)) ||
+            beneficiaries.contains(&Address::ZERO)
+        {
+            return Err(
+                TreasuryError::InvalidParameter(InvalidParameter {
+                    point: 1,
+                })
+            );
+        }
+
+        while self.beneficiaries.pop().is_some() {}
+        while self.beneficiary_bps.pop().is_some() {}
+        for (beneficiary, share) in beneficiaries.iter().zip(shares_bps.iter()) {
+            self.beneficiaries.push(*beneficiary);
+            self.beneficiary_bps.push(*share);
+        }
+
+        evm::log(SetBeneficiaries {
+            beneficiaries,
+            shares_bps,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    // returns the beneficiaries of the proceeds and their shares in basis points
+    pub fn get_beneficiaries(&self) -> (Vec<Address>, Vec<U256>) {
+        (
+            (0..self.beneficiaries.len()).filter_map(|i| self.beneficiaries.get(i)).collect(),
+            (0..self.beneficiary_bps.len()).filter_map(|i| self.beneficiary_bps.get(i)).collect(),
+        )
+    }
+
+    // sends `amount` wei of the proceeds owed to `to` (admin only)
+    pub fn withdraw_proceeds(&mut self, to: Address, amount: U256) -> Result<(), TreasuryError> {
+        self.check_admin()?;
+        let balance = self.proceeds.get(to);
+        if amount > balance {
+            return Err(
+                TreasuryError::InsufficientProceeds(InsufficientProceeds {
+                    beneficiary: to,
+                    have: balance,
+                    want: amount,
+                })
+            );
+        }
+
+        self.proceeds.setter(to).set(balance - amount);
+        self.treasury.set(self.treasury.get() - amount);
+
+        call(Call::new_in(self).value(amount), to, &[]).map_err(|_e| {
+            TreasuryError::InvalidParameter(InvalidParameter {
+                point: 2,
+            })
+        })?;
+
+        evm::log(ProceedsWithdrawn {
+            to,
+            amount,
+            balance: balance - amount,
+            time: block::timestamp() as u64,
+        });
+        Ok(())
+    }
+
+    // wei of proceeds owed to `beneficiary`
+    pub fn proceeds_of(&self, beneficiary: Address) -> U256 {
+        self.proceeds.get(beneficiary)
+    }
+
+    // wei of proceeds held for all beneficiaries
+    pub fn treasury(&self) -> U256 {
+        self.treasury.get()
+    }
+}
+
+// helper functions
+
+impl Treasury {
+    // records `value` wei of proceeds, split between the beneficiaries;
+    // rounding dust goes to the last beneficiary
+    pub fn accrue_proceeds(&mut self, value: U256) {
+        if value == U256::ZERO {
+            return;
+        }
+        self.treasury.set(self.treasury.get() + value);
+
+        let count = self.beneficiaries.len();
+        let mut left = value;
+        for i in 0..count {
+            let (beneficiary, amount) = if i == count - 1 {
+                (self.beneficiaries.get(i).unwrap_or_default(), left)
+            } else {
+                let share = self.beneficiary_bps.get(i).unwrap_or_default();
+                (self.beneficiaries.get(i).unwrap_or_default(), (value * share) / U256::from(BPS))
+            };
+            left -= amount;
+
+            let balance = self.proceeds.get(beneficiary) + amount;
+            self.proceeds.setter(beneficiary).set(balance);
+            evm::log(ProceedsAccrued {
+                beneficiary,
+                amount,
+                balance,
+                time: block::timestamp() as u64,
+            });
+        }
+    }
+
+    pub fn check_admin(&mut self) -> Result<bool, TreasuryError> {
+        let default_x = Address::from([0x00; 20]);
+        if self.admin.get() != default_x && msg::sender() != self.admin.get() {
+            return Err(
+                TreasuryError::InvalidParameter(InvalidParameter {
+                    point: 0,
+                })
+            );
+        } else if self.admin.get() == default_x {
+            self.admin.set(msg::sender());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,206 @@
+//! Unit tests run the contract against an in-memory stand-in of the Stylus host:
+//! storage lives in a per-thread map, the caller, block and contract address
+//! are the fixed values below, and there are no other contracts to call.
+
+use super::*;
+
+// the account every call is made from; it becomes the admin on its first admin call
+const SENDER: Address = Address::new([0x11; 20]);
+const TREASURY: Address = Address::new([0x22; 20]);
+const NOW: u64 = 1_700_000_000;
+const BLOCK: u64 = 1_000;
+
+mod host {
+    use super::{ Address, BLOCK, NOW, SENDER, TREASURY };
+    use sha3::{ Digest, Keccak256 };
+    use std::{ cell::RefCell, collections::HashMap };
+
+    thread_local! {
+        static STORAGE: RefCell<HashMap<[u8; 32], [u8; 32]>> = RefCell::new(HashMap::new());
+    }
+
+    unsafe fn write(dest: *mut u8, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+    }
+
+    unsafe fn address(dest: *mut u8, address: Address) {
+        write(dest, address.as_slice());
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_load_bytes32(key: *const u8, dest: *mut u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = STORAGE.with(|s| s.borrow().get(&key).copied().unwrap_or_default());
+        write(dest, &value);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn storage_cache_bytes32(key: *const u8, value: *const u8) {
+        let key = *(key as *const [u8; 32]);
+        let value = *(value as *const [u8; 32]);
+        STORAGE.with(|s| s.borrow_mut().insert(key, value));
+    }
+
+    #[no_mangle]
+    extern "C" fn storage_flush_cache(_clear: bool) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn native_keccak256(bytes: *const u8, len: usize, output: *mut u8) {
+        let input = std::slice::from_raw_parts(bytes, len);
+        write(output, &Keccak256::digest(input));
+    }
+
+    #[no_mangle]
+    extern "C" fn emit_log(_data: *const u8, _len: usize, _topics: usize) {}
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_sender(sender: *mut u8) {
+        address(sender, SENDER);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn msg_value(value: *mut u8) {
+        write(value, &[0; 32]);
+    }
+
+    #[no_mangle]
+    extern "C" fn msg_reentrant() -> bool {
+        false
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn contract_address(dest: *mut u8) {
+        address(dest, TREASURY);
+    }
+
+    #[no_mangle]
+    extern "C" fn block_timestamp() -> u64 {
+        NOW
+    }
+
+    #[no_mangle]
+    extern "C" fn block_number() -> u64 {
+        BLOCK
+    }
+
+    #[no_mangle]
+    extern "C" fn chainid() -> u64 {
+        42161
+    }
+
+    #[no_mangle]
+    extern "C" fn account_code_size(_address: *const u8) -> usize {
+        0
+    }
+
+    // there are no other contracts: every call reverts without return data
+    #[no_mangle]
+    unsafe extern "C" fn call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _value: *const u8,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn delegate_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn static_call_contract(
+        _contract: *const u8,
+        _calldata: *const u8,
+        _calldata_len: usize,
+        _gas: u64,
+        return_data_len: *mut usize
+    ) -> u8 {
+        *return_data_len = 0;
+        1
+    }
+
+    #[no_mangle]
+    extern "C" fn read_return_data(_dest: *mut u8, _offset: usize, _size: usize) -> usize {
+        0
+    }
+
+    #[no_mangle]
+    extern "C" fn return_data_size() -> usize {
+        0
+    }
+}
+
+
+// a fresh treasury; storage is empty on every test thread
+fn treasury() -> Treasury {
+    unsafe { <Treasury as stylus_sdk::storage::StorageType>::new(U256::ZERO, 0) }
+}
+
+fn ok<T>(result: Result<T, TreasuryError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("call reverted: 0x{}", alloy_primitives::hex::encode(Vec::<u8>::from(e))),
+    }
+}
+
+fn account(byte: u8) -> Address {
+    Address::new([byte; 20])
+}
+
+fn wei(amount: u64) -> U256 {
+    U256::from(amount)
+}
+
+#[test]
+fn deposits_wait_for_the_beneficiaries() {
+    let mut treasury = treasury();
+    let refused = treasury.deposit();
+    assert!(matches!(refused, Err(TreasuryError::InvalidParameter(InvalidParameter { point: 3 }))));
+
+    ok(treasury.set_beneficiaries(vec![account(1)], vec![wei(10_000)]));
+    ok(treasury.deposit());
+    assert_eq!(treasury.get_beneficiaries().0, vec![account(1)]);
+}
+
+#[test]
+fn proceeds_are_split_with_the_dust_to_the_last_beneficiary() {
+    let mut treasury = treasury();
+    let shares = vec![wei(3_333), wei(3_333), wei(3_334)];
+    ok(treasury.set_beneficiaries(vec![account(1), account(2), account(3)], shares));
+    treasury.accrue_proceeds(wei(100));
+    assert_eq!(treasury.proceeds_of(account(1)), wei(33));
+    assert_eq!(treasury.proceeds_of(account(2)), wei(33));
+    assert_eq!(treasury.proceeds_of(account(3)), wei(34));
+    assert_eq!(treasury.treasury(), wei(100));
+}
+
+#[test]
+fn shares_must_sum_to_the_whole() {
+    let mut treasury = treasury();
+    let result = treasury.set_beneficiaries(vec![account(1), account(2)], vec![wei(5_000), wei(4_999)]);
+    assert!(matches!(result, Err(TreasuryError::InvalidParameter(InvalidParameter { point: 1 }))));
+    let result = treasury.set_beneficiaries(vec![account(1)], vec![wei(5_000), wei(5_000)]);
+    assert!(matches!(result, Err(TreasuryError::InvalidParameter(InvalidParameter { point: 1 }))));
+}
+
+#[test]
+fn withdrawals_are_limited_to_what_is_owed() {
+    let mut treasury = treasury();
+    ok(treasury.set_beneficiaries(vec![account(1)], vec![wei(10_000)]));
+    treasury.accrue_proceeds(wei(100));
+    let result = treasury.withdraw_proceeds(account(1), wei(101));
+    assert!(matches!(result, Err(TreasuryError::InsufficientProceeds(_))));
+    assert_eq!(treasury.proceeds_of(account(1)), wei(100));
+}
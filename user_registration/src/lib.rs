@@ -2,7 +2,7 @@
 extern crate alloc;
 
 use alloy_primitives::{ Address, U32, U256 };
-use stylus_sdk::{ prelude::*, block, contract, msg };
+use stylus_sdk::{ prelude::*, block, msg };
 use stylus_sdk::call::Call;
 use alloy_sol_types::sol;
 
@@ -11,6 +11,10 @@ const AIRDROP: u32 = 200_000;
 // this will be the factor of reduction of the numbers of tokewns a user can get
 const SUBX: u32 = 32;
 
+// the airdrop is locked and vests linearly so new accounts can not dump it at once
+const AIRDROP_CLIFF: u64 = 7 * 24 * 60 * 60; // nothing vests in the first week
+const AIRDROP_VESTING: u64 = 90 * 24 * 60 * 60; // fully vested after 90 days
+
 sol_storage! {
     #[entrypoint]
     pub struct Users {
@@ -47,7 +51,7 @@ sol_storage! {
 sol_interface! {
     // interface that allows this contract to perfrom minting to users wallet
     interface IErc20 {
-        function mintTo(address to, uint256 value) external;
+        function mintLocked(address to, uint256 value, uint64 cliff, uint64 duration) external;
     }
 }
 sol! {
@@ -159,8 +163,8 @@ impl Users {
     pub fn mint_tkn(&mut self, tkn: U256, address: Address) {
         let meta_date_contract = IErc20::new(*self.erc20);
         let config = Call::new_in(self);
-        let _ = meta_date_contract
-            .mint_to(config, address, tkn)
+        meta_date_contract
+            .mint_locked(config, address, tkn, AIRDROP_CLIFF, AIRDROP_VESTING)
             .expect("Failed to call on MetaDate_contract");
     }
 
@@ -174,10 +178,8 @@ impl Users {
             );
         } else if self.admin.get() == default_x {
             self.admin.set(msg::sender());
-            return Ok(true);
-        } else {
-            return Ok(true);
         }
+        Ok(true)
     }
 
     // this is the reduction factor